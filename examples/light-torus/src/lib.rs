@@ -1,5 +1,5 @@
 use cgmath::{vec3, Rad};
-use futures::stream::StreamExt as _;
+use futures::{future::Either, stream::StreamExt as _};
 use napier::{
     meshes,
     window::{event::Event, Canvas},
@@ -25,7 +25,7 @@ pub async fn start() -> Result<(), JsValue> {
 
     // シーンの設定
     let mut scene = Scene::new();
-    scene.light = Some(Light::point(0.0, 0.0, 10.0));
+    scene.lights.push(Light::point(0.0, 0.0, 10.0));
     scene.add(&objects.texture);
     scene.add(&objects.transparent_rect);
 
@@ -47,16 +47,19 @@ pub async fn start() -> Result<(), JsValue> {
         loop {
             objects.texture.transform.rotate.angle.add(Rad(0.02));
 
-            renderer.render(&scene, &camera);
+            if let Err(e) = renderer.render(&scene, &camera) {
+                return e;
+            }
 
             gloo_timers::future::TimeoutFuture::new(1000 / 60).await;
         }
     };
     futures::pin_mut!(rendering_fut);
 
-    futures::future::select(event_handler_fut, rendering_fut).await;
-
-    Ok(())
+    match futures::future::select(event_handler_fut, rendering_fut).await {
+        Either::Right((e, _)) => Err(e.into()),
+        Either::Left(_) => Ok(()),
+    }
 }
 
 pub struct Objects {
@@ -73,10 +76,11 @@ impl Objects {
             4.0,
             Color::rgba(255, 255, 255, 1.0),
             texture,
-        ));
+        )?);
         texture_obj.transform.rotate.axis.set(0.0, 1.0, 1.0);
 
-        let transparent_rect = Object::new(meshes::rect(6.0, 6.0, Color::rgba(100, 100, 100, 0.3)));
+        let transparent_rect =
+            Object::new(meshes::rect(6.0, 6.0, Color::rgba(100, 100, 100, 0.3))?);
         transparent_rect.transform.pos.z.set(-1.0);
 
         Ok(Objects {
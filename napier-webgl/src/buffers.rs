@@ -1,10 +1,20 @@
-use super::context;
-use std::marker::PhantomData;
+use super::{
+    context::{self, ContextId},
+    error::Error,
+    resource::{self, ResourceKind},
+};
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+};
 use web_sys::WebGlRenderingContext as GL;
 
+/// 頂点バッファーオブジェクト
+/// 転送したデータはCPU側にも保持し、Contextが復元されたときに転送し直す
 #[derive(Debug, PartialEq)]
 pub struct VBO<T> {
-    buf: web_sys::WebGlBuffer,
+    raw: RawBuffer,
+    data: RefCell<Vec<f32>>,
     _type: PhantomData<T>,
 }
 
@@ -12,55 +22,66 @@ impl<T> VBO<T>
 where
     T: AsRef<[f32]>,
 {
-    pub fn new() -> Self {
-        context::with(|ctx| {
-            // bufferの作成
-            let buf = ctx.create_buffer().unwrap();
-
-            VBO {
-                buf,
-                _type: PhantomData,
-            }
+    pub fn new() -> Result<Self, Error> {
+        Ok(VBO {
+            raw: RawBuffer::new(GL::ARRAY_BUFFER)?,
+            data: RefCell::new(Vec::new()),
+            _type: PhantomData,
         })
     }
 
-    pub fn with_data(data: &T) -> Self {
-        let vbo = VBO::new();
-        vbo.set_data(data);
-        vbo
+    pub fn with_data(data: &T) -> Result<Self, Error> {
+        let vbo = VBO::new()?;
+        vbo.set_data(data)?;
+        Ok(vbo)
     }
 
-    pub fn bind(&self) {
-        context::with(|ctx| {
-            ctx.bind_buffer(GL::ARRAY_BUFFER, Some(&self.buf));
-        })
+    /// bufferを生成したContext
+    pub fn context(&self) -> ContextId {
+        self.raw.context
+    }
+
+    /// Contextが既に破棄されている場合は `Error::ContextUnavailable` を返す
+    pub fn bind(&self) -> Result<(), Error> {
+        if self.raw.restore()? {
+            self.upload()?;
+        }
+        self.raw.bind()
     }
 
     pub fn unbind(&self) {
-        context::with(|ctx| {
-            ctx.bind_buffer(GL::ARRAY_BUFFER, None);
-        })
+        self.raw.unbind();
     }
 
-    pub fn set_data(&self, data: &T) {
-        self.bind();
+    pub fn set_data(&self, data: &T) -> Result<(), Error> {
+        *self.data.borrow_mut() = data.as_ref().to_vec();
+        self.raw.restore()?;
+        self.upload()
+    }
+
+    fn upload(&self) -> Result<(), Error> {
+        self.raw.bind()?;
 
-        context::with(|ctx| {
-            let js_array = js_sys::Float32Array::from(data.as_ref());
+        context::try_with_id(self.raw.context, |ctx| {
+            let js_array = js_sys::Float32Array::from(self.data.borrow().as_slice());
 
             // bufferにデータをセット
             ctx.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_array, GL::STATIC_DRAW);
-        });
+            ctx.count_buffer_upload();
+        })?;
 
-        self.unbind();
+        self.raw.unbind();
+        Ok(())
     }
 }
 
 /// インデックスバッファーオブジェクト
 /// 型パラメータ `T` は `Vec3<i16>` などを想定
+/// 転送したデータはCPU側にも保持し、Contextが復元されたときに転送し直す
 #[derive(Debug, PartialEq)]
 pub struct IBO<T> {
-    buf: web_sys::WebGlBuffer,
+    raw: RawBuffer,
+    data: RefCell<Vec<i16>>,
     _type: PhantomData<T>,
 }
 
@@ -68,41 +89,48 @@ impl<T> IBO<T>
 where
     T: AsRef<[i16]>,
 {
-    pub fn new() -> Self {
-        context::with(|ctx| {
-            // bufferの作成
-            let buf = ctx.create_buffer().unwrap();
-
-            IBO {
-                buf,
-                _type: PhantomData,
-            }
+    pub fn new() -> Result<Self, Error> {
+        Ok(IBO {
+            raw: RawBuffer::new(GL::ELEMENT_ARRAY_BUFFER)?,
+            data: RefCell::new(Vec::new()),
+            _type: PhantomData,
         })
     }
 
-    pub fn with_data(data: &T) -> Self {
-        let vbo = IBO::new();
-        vbo.set_data(data);
-        vbo
+    pub fn with_data(data: &T) -> Result<Self, Error> {
+        let vbo = IBO::new()?;
+        vbo.set_data(data)?;
+        Ok(vbo)
     }
 
-    pub fn bind(&self) {
-        context::with(|ctx| {
-            ctx.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&self.buf));
-        })
+    /// bufferを生成したContext
+    pub fn context(&self) -> ContextId {
+        self.raw.context
+    }
+
+    /// Contextが既に破棄されている場合は `Error::ContextUnavailable` を返す
+    pub fn bind(&self) -> Result<(), Error> {
+        if self.raw.restore()? {
+            self.upload()?;
+        }
+        self.raw.bind()
     }
 
     pub fn unbind(&self) {
-        context::with(|ctx| {
-            ctx.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, None);
-        })
+        self.raw.unbind();
     }
 
-    pub fn set_data(&self, data: &T) {
-        self.bind();
+    pub fn set_data(&self, data: &T) -> Result<(), Error> {
+        *self.data.borrow_mut() = data.as_ref().to_vec();
+        self.raw.restore()?;
+        self.upload()
+    }
+
+    fn upload(&self) -> Result<(), Error> {
+        self.raw.bind()?;
 
-        context::with(|ctx| {
-            let js_array = js_sys::Int16Array::from(data.as_ref());
+        context::try_with_id(self.raw.context, |ctx| {
+            let js_array = js_sys::Int16Array::from(self.data.borrow().as_slice());
 
             // bufferにデータをセット
             ctx.buffer_data_with_array_buffer_view(
@@ -110,8 +138,129 @@ where
                 &js_array,
                 GL::STATIC_DRAW,
             );
+            ctx.count_buffer_upload();
+        })?;
+
+        self.raw.unbind();
+        Ok(())
+    }
+}
+
+/// VBOとIBOに共通する、bufferオブジェクトそのもの
+#[derive(Debug, PartialEq)]
+struct RawBuffer {
+    // bufferを生成したContext
+    context: ContextId,
+    target: u32,
+    // Contextが失われている間に生成された場合は `None`
+    buf: RefCell<Option<web_sys::WebGlBuffer>>,
+    // bufferを生成したContextの世代
+    generation: Cell<u64>,
+}
+
+impl RawBuffer {
+    /// Contextが失われている間は、bufferを生成せずに `None` を保持する
+    /// その場合は、Contextが復元されたときに生成される
+    fn new(target: u32) -> Result<Self, Error> {
+        let raw = context::try_with(|ctx| {
+            // bufferの作成
+            let buf = ctx.create_buffer();
+            if buf.is_none() && !ctx.is_lost() {
+                return Err(Error::ResourceCreation("buffer"));
+            }
+            Ok(RawBuffer {
+                context: ctx.id(),
+                target,
+                buf: RefCell::new(buf),
+                generation: Cell::new(ctx.generation()),
+            })
+        })??;
+        resource::count_created(ResourceKind::Buffer);
+        Ok(raw)
+    }
+
+    /// Contextが復元されていれば、bufferを作り直して `true` を返す
+    /// 作り直したbufferは空なので、データを転送し直す必要がある
+    fn restore(&self) -> Result<bool, Error> {
+        context::try_with_id(self.context, |ctx| {
+            if self.generation.get() == ctx.generation() {
+                return false;
+            }
+            *self.buf.borrow_mut() = ctx.create_buffer();
+            self.generation.set(ctx.generation());
+            true
+        })
+    }
+
+    fn bind(&self) -> Result<(), Error> {
+        context::try_with_id(self.context, |ctx| {
+            ctx.bind_buffer(self.target, self.buf.borrow().as_ref());
+        })
+    }
+
+    /// Contextが既に破棄されている場合は何もしない
+    fn unbind(&self) {
+        let _ = context::try_with_id(self.context, |ctx| {
+            ctx.bind_buffer(self.target, None);
         });
+    }
+}
+
+impl Drop for RawBuffer {
+    fn drop(&mut self) {
+        let buf = self.buf.borrow_mut().take();
+        context::delete_resource(
+            self.context,
+            self.generation.get(),
+            ResourceKind::Buffer,
+            move |ctx| ctx.delete_buffer(buf.as_ref()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_upload_again_after_restore() {
+        let id = context::create(&test_util::canvas()).unwrap();
+        context::make_current(id);
+
+        let vbo = VBO::with_data(&vec![1.0, 2.0, 3.0]).unwrap();
+        let old_buf = vbo.raw.buf.borrow().clone();
+        let uploads = context::with(|ctx| ctx.buffer_uploads());
+
+        // 世代が変わっていなければ、転送し直さない
+        vbo.bind().unwrap();
+        assert_eq!(context::with(|ctx| ctx.buffer_uploads()), uploads);
+
+        context::with(|ctx| ctx.on_restored());
+        vbo.bind().unwrap();
+        assert_eq!(context::with(|ctx| ctx.buffer_uploads()), uploads + 1);
+        assert_ne!(*vbo.raw.buf.borrow(), old_buf);
+
+        // 作り直したbufferに、保持していたデータが転送されている
+        let size = context::with(|ctx| ctx.get_buffer_parameter(GL::ARRAY_BUFFER, GL::BUFFER_SIZE));
+        assert_eq!(size.as_f64(), Some(3.0 * 4.0));
+
+        drop(vbo);
+        context::destroy(id);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_bind_after_destroy() {
+        let id = context::create(&test_util::canvas()).unwrap();
+        context::make_current(id);
 
-        self.unbind();
+        let vbo = VBO::with_data(&vec![1.0, 2.0, 3.0]).unwrap();
+        context::destroy(id);
+        assert_eq!(vbo.bind(), Err(Error::ContextUnavailable(None)));
+        assert_eq!(
+            vbo.set_data(&vec![4.0]),
+            Err(Error::ContextUnavailable(None))
+        );
     }
 }
@@ -0,0 +1,101 @@
+use web_sys::WebGlRenderingContext as GL;
+
+/// 使用しているWebGLのバージョン
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    WebGl1,
+    WebGl2,
+}
+
+/// Contextの生成時に、どのバージョンのWebGLを使うか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BackendPreference {
+    /// 常にWebGL1を使う
+    WebGl1,
+    /// WebGL2が使えればWebGL2を、使えなければWebGL1を使う
+    PreferWebGl2,
+    /// WebGL2を使う。使えない場合はContextの生成に失敗する
+    WebGl2,
+}
+
+/// Contextで使える機能のうち、napier-webglが使い分けているもの
+///
+/// シェーダーはGLSL ES 1.00、頂点インデックスは16bitで扱うため、
+/// WebGL2でもそれらに関わる機能は使わない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub backend: Backend,
+    /// 縦横が2の冪乗でないテクスチャでのミップマップとリピート
+    /// WebGL1では、2の冪乗でないテクスチャはミップマップを使わず、クランプする場合のみ使える
+    /// `GlTexture` はこの値に応じて、ミップマップを使うかどうかを切り替える
+    pub npot_textures: bool,
+    /// テクスチャの一辺の最大ピクセル数
+    /// `GlTexture` はこれを超えるサイズの画像をエラーにする
+    pub max_texture_size: i32,
+}
+
+impl Capabilities {
+    /// `gl` で使える機能を調べる
+    pub(crate) fn detect(gl: &GL, backend: Backend) -> Capabilities {
+        let max_texture_size = gl
+            .get_parameter(GL::MAX_TEXTURE_SIZE)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as i32;
+
+        Capabilities {
+            backend,
+            npot_textures: backend == Backend::WebGl2,
+            max_texture_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context, error::Error, test_util};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_detect_webgl1() {
+        let id = context::create_with(&test_util::canvas(), BackendPreference::WebGl1).unwrap();
+        let capabilities = context::with_id(id, |ctx| *ctx.capabilities());
+
+        assert_eq!(capabilities.backend, Backend::WebGl1);
+        assert!(!capabilities.npot_textures);
+        // WebGL1で保証されている最小値
+        assert!(capabilities.max_texture_size >= 64);
+
+        context::destroy(id);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_detect_prefer_webgl2() {
+        let canvas = test_util::canvas();
+        let id = context::create_with(&canvas, BackendPreference::PreferWebGl2).unwrap();
+        let (capabilities, has_gl2) =
+            context::with_id(id, |ctx| (*ctx.capabilities(), ctx.gl2().is_some()));
+
+        // WebGL2が使えない環境ではWebGL1になる
+        assert_eq!(has_gl2, capabilities.backend == Backend::WebGl2);
+        assert_eq!(
+            capabilities.npot_textures,
+            capabilities.backend == Backend::WebGl2
+        );
+
+        context::destroy(id);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_webgl2_on_webgl1_canvas() {
+        // WebGL1のContextを取得したcanvasからは、WebGL2のContextは取得できない
+        let canvas = test_util::canvas();
+        let id = context::create_with(&canvas, BackendPreference::WebGl1).unwrap();
+        assert_eq!(
+            context::create_with(&canvas, BackendPreference::WebGl2),
+            Err(Error::ContextUnavailable(Some(Backend::WebGl2)))
+        );
+        context::destroy(id);
+    }
+}
@@ -1,42 +1,409 @@
-use super::program::GlProgram;
+use super::{
+    capabilities::{Backend, BackendPreference, Capabilities},
+    error::Error,
+    program::GlProgram,
+    resource::{self, ResourceKind},
+};
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    collections::HashMap,
     ops::{Deref, DerefMut},
+    rc::Rc,
 };
-use wasm_bindgen::{JsCast as _, JsValue};
+use wasm_bindgen::{closure::Closure, JsCast as _};
 use web_sys::WebGlRenderingContext as GL;
 
 pub struct Context {
+    id: ContextId,
+    canvas: web_sys::HtmlCanvasElement,
+    // WebGL2の場合も、WebGL1と共通のAPIはこちらから呼び出す
     gl: GL,
+    gl2: Option<web_sys::WebGl2RenderingContext>,
+    capabilities: Capabilities,
     enabled_vertex_attrib_locations: Vec<u32>,
+    generation: u64,
+    buffer_uploads: usize,
+    _loss_listener: ContextLossListener,
 }
 
+/// 生成したContextを識別するID
+///
+/// 複数のcanvasに描画する場合は、canvasごとに `create` でContextを生成し、
+/// `make_current` で操作対象のContextを切り替える。
+/// 1つのcanvasに対応するContextは常に1つだけであり、IDもcanvasごとに1つになる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContextId(u64);
+
 thread_local! {
-    static GLOBAL_CONTEXT_CELL: RefCell<Option<Context>> = RefCell::new(None);
+    static CONTEXTS: RefCell<HashMap<ContextId, Rc<RefCell<Context>>>> = RefCell::new(HashMap::new());
+    // `with` で操作するContext
+    static CURRENT_CONTEXT: Cell<Option<ContextId>> = const { Cell::new(None) };
+    // `initialize` が生成したContext。別のcanvasで初期化し直したときに破棄する
+    static INITIALIZED_CONTEXT: Cell<Option<ContextId>> = const { Cell::new(None) };
+    static NEXT_CONTEXT_ID: Cell<u64> = const { Cell::new(0) };
+    // これまでに生成、復元したContextの数
+    static CONTEXT_GENERATION: Cell<u64> = const { Cell::new(0) };
+    // `with` の実行中にDropされたため、削除を延期しているGPUリソース
+    static DEFERRED_DELETIONS: RefCell<Vec<Deletion>> = const { RefCell::new(Vec::new()) };
 }
 
-pub fn initialize(canvas: &web_sys::HtmlCanvasElement) -> Result<(), JsValue> {
-    let gl = canvas.get_context("webgl")?.unwrap().dyn_into::<GL>()?;
+/// canvasからWebGL1のContextを取得し、操作対象のContextにする
+/// canvasが1つの場合に使う
+///
+/// 別のcanvasで初期化し直した場合、以前に `initialize` で生成したContextは破棄される。
+/// `create` で生成したContextは破棄されない。
+/// 同じcanvasで初期化し直した場合は、既存のContextをそのまま使う。
+pub fn initialize(canvas: &web_sys::HtmlCanvasElement) -> Result<(), Error> {
+    initialize_with(canvas, BackendPreference::WebGl1)
+}
+
+/// `preference` に従ってcanvasからWebGLのContextを取得し、操作対象のContextにする
+/// 初期化し直した場合の動作は `initialize` と同じ
+pub fn initialize_with(
+    canvas: &web_sys::HtmlCanvasElement,
+    preference: BackendPreference,
+) -> Result<(), Error> {
+    let created_by_other = find(canvas).is_some_and(|id| Some(id) != initialized());
+    let id = create_with(canvas, preference)?;
+    if let Some(prev) = initialized() {
+        if prev != id {
+            destroy(prev);
+        }
+    }
+    // `create` で生成済みだったContextは、呼び出し側が管理する
+    if !created_by_other {
+        INITIALIZED_CONTEXT.with(|initialized| initialized.set(Some(id)));
+    }
+    make_current(id);
+    Ok(())
+}
+
+/// canvasから新たにWebGL1のContextを取得する
+pub fn create(canvas: &web_sys::HtmlCanvasElement) -> Result<ContextId, Error> {
+    create_with(canvas, BackendPreference::WebGl1)
+}
+
+/// `preference` に従って、canvasから新たにWebGLのContextを取得する
+/// 実際に使われるバージョンは `Context::capabilities` で確認できる
+/// 操作対象のContextがまだ無い場合は、生成したContextを操作対象にする
+/// Contextが失われたときに復元されるよう、canvasのイベントを監視する
+///
+/// `canvas` のContextが既に生成されている場合は、そのIDを返す。
+/// ただし既存のContextがWebGL1で、`preference` が `WebGl2` の場合はエラーになる。
+pub fn create_with(
+    canvas: &web_sys::HtmlCanvasElement,
+    preference: BackendPreference,
+) -> Result<ContextId, Error> {
+    // ブラウザは同じcanvasに対して常に同じContextを返すため、
+    // 別のContextとして登録すると、同じGPUの状態を二重に管理することになる
+    if let Some(id) = find(canvas) {
+        let backend = with_id(id, |ctx| ctx.capabilities.backend);
+        if preference == BackendPreference::WebGl2 && backend == Backend::WebGl1 {
+            return Err(Error::ContextUnavailable(Some(Backend::WebGl2)));
+        }
+        if current().is_none() {
+            make_current(id);
+        }
+        return Ok(id);
+    }
+
+    let gl2 = match preference {
+        BackendPreference::WebGl1 => None,
+        BackendPreference::PreferWebGl2 | BackendPreference::WebGl2 => canvas
+            .get_context("webgl2")?
+            .and_then(|gl2| gl2.dyn_into::<web_sys::WebGl2RenderingContext>().ok()),
+    };
+    let gl = match gl2.as_ref() {
+        // WebGL2RenderingContextはWebGL1のAPIを全て持つため、WebGL1として扱う
+        Some(gl2) => gl2.clone().unchecked_into::<GL>(),
+        None if preference == BackendPreference::WebGl2 => {
+            return Err(Error::ContextUnavailable(Some(Backend::WebGl2)));
+        }
+        None => canvas
+            .get_context("webgl")?
+            .and_then(|gl| gl.dyn_into::<GL>().ok())
+            .ok_or(Error::ContextUnavailable(Some(Backend::WebGl1)))?,
+    };
+    let backend = match gl2 {
+        Some(_) => Backend::WebGl2,
+        None => Backend::WebGl1,
+    };
+
+    let id = NEXT_CONTEXT_ID.with(|next| {
+        next.set(next.get() + 1);
+        ContextId(next.get())
+    });
 
     let context = Context {
+        id,
+        canvas: canvas.clone(),
+        capabilities: Capabilities::detect(&gl, backend),
         gl,
+        gl2,
         enabled_vertex_attrib_locations: Vec::new(),
+        generation: next_generation(),
+        buffer_uploads: 0,
+        _loss_listener: ContextLossListener::listen(canvas, id)?,
     };
 
-    GLOBAL_CONTEXT_CELL.with(|cell| cell.replace(Some(context)));
+    CONTEXTS.with(|contexts| {
+        contexts
+            .borrow_mut()
+            .insert(id, Rc::new(RefCell::new(context)))
+    });
+    if current().is_none() {
+        make_current(id);
+    }
 
-    Ok(())
+    Ok(id)
+}
+
+/// `with` で操作するContextを切り替える
+pub fn make_current(id: ContextId) {
+    CURRENT_CONTEXT.with(|current| current.set(Some(id)));
+}
+
+/// `with` で操作するContext
+pub fn current() -> Option<ContextId> {
+    CURRENT_CONTEXT.with(Cell::get)
+}
+
+/// Contextを破棄する
+/// このContextで生成したGPUリソースは、以降使用できない
+pub fn destroy(id: ContextId) {
+    let removed = CONTEXTS.with(|contexts| contexts.borrow_mut().remove(&id));
+    if current() == Some(id) {
+        CURRENT_CONTEXT.with(|current| current.set(None));
+    }
+    if initialized() == Some(id) {
+        INITIALIZED_CONTEXT.with(|initialized| initialized.set(None));
+    }
+    drop(removed);
+}
+
+fn initialized() -> Option<ContextId> {
+    INITIALIZED_CONTEXT.with(Cell::get)
 }
 
+fn next_generation() -> u64 {
+    CONTEXT_GENERATION.with(|g| {
+        g.set(g.get() + 1);
+        g.get()
+    })
+}
+
+/// `canvas` から生成されたContextのIDを返す
+fn find(canvas: &web_sys::HtmlCanvasElement) -> Option<ContextId> {
+    CONTEXTS.with(|contexts| {
+        contexts.borrow().values().find_map(|ctx| {
+            let ctx = ctx.try_borrow().ok()?;
+            if &ctx.canvas == canvas {
+                Some(ctx.id)
+            } else {
+                None
+            }
+        })
+    })
+}
+
+fn get(id: ContextId) -> Option<Rc<RefCell<Context>>> {
+    CONTEXTS
+        .try_with(|contexts| contexts.borrow().get(&id).cloned())
+        .ok()
+        .flatten()
+}
+
+/// `webglcontextlost` と `webglcontextrestored` を監視する
+/// Dropされると監視をやめる
+struct ContextLossListener {
+    canvas: web_sys::HtmlCanvasElement,
+    on_lost: Closure<dyn FnMut(web_sys::Event)>,
+    on_restored: Closure<dyn FnMut(web_sys::Event)>,
+}
+
+impl ContextLossListener {
+    fn listen(canvas: &web_sys::HtmlCanvasElement, id: ContextId) -> Result<Self, Error> {
+        // デフォルトの動作を抑制しないと、Contextは復元されない
+        let on_lost = Closure::wrap(
+            Box::new(|event: web_sys::Event| event.prevent_default()) as Box<dyn FnMut(_)>
+        );
+
+        let on_restored = Closure::wrap(Box::new(move |_: web_sys::Event| {
+            if let Some(ctx) = get(id) {
+                ctx.borrow_mut().on_restored();
+            }
+        }) as Box<dyn FnMut(_)>);
+
+        canvas.add_event_listener_with_callback(
+            "webglcontextlost",
+            on_lost.as_ref().unchecked_ref(),
+        )?;
+        canvas.add_event_listener_with_callback(
+            "webglcontextrestored",
+            on_restored.as_ref().unchecked_ref(),
+        )?;
+
+        Ok(ContextLossListener {
+            canvas: canvas.clone(),
+            on_lost,
+            on_restored,
+        })
+    }
+}
+
+impl Drop for ContextLossListener {
+    fn drop(&mut self) {
+        let _ = self.canvas.remove_event_listener_with_callback(
+            "webglcontextlost",
+            self.on_lost.as_ref().unchecked_ref(),
+        );
+        let _ = self.canvas.remove_event_listener_with_callback(
+            "webglcontextrestored",
+            self.on_restored.as_ref().unchecked_ref(),
+        );
+    }
+}
+
+/// 操作対象のContextに対して `func` を実行する
 /// panic if uninitialized
 pub fn with<F, T>(func: F) -> T
 where
     F: FnOnce(&mut Context) -> T,
 {
-    GLOBAL_CONTEXT_CELL.with(|cell| func(cell.borrow_mut().as_mut().unwrap()))
+    with_id(current().expect("context is not initialized"), func)
+}
+
+/// 操作対象のContextに対して `func` を実行する
+/// 操作対象のContextが無い場合は `Error::ContextUnavailable` を返す
+pub fn try_with<F, T>(func: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut Context) -> T,
+{
+    try_with_id(current().ok_or(Error::ContextUnavailable(None))?, func)
+}
+
+/// 指定したContextに対して `func` を実行する
+/// panic if the context has been destroyed
+pub fn with_id<F, T>(id: ContextId, func: F) -> T
+where
+    F: FnOnce(&mut Context) -> T,
+{
+    try_with_id(id, func).expect("context has been destroyed")
+}
+
+/// 指定したContextに対して `func` を実行する
+/// Contextが既に破棄されている場合は `Error::ContextUnavailable` を返す
+pub fn try_with_id<F, T>(id: ContextId, func: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut Context) -> T,
+{
+    let ctx = get(id).ok_or(Error::ContextUnavailable(None))?;
+    let result = func(&mut ctx.borrow_mut());
+    run_deferred_deletions();
+    Ok(result)
+}
+
+/// `with` の実行中にDropされたGPUリソースの削除
+struct Deletion {
+    context: ContextId,
+    generation: u64,
+    kind: ResourceKind,
+    delete: Box<dyn FnOnce(&Context)>,
+}
+
+/// GPUリソースを削除する。各リソースのDropから呼び出す
+///
+/// Contextが既に破棄・復元されている場合は、GPUリソースも既に失われているため `delete` は実行しない。
+/// 同じContextに対する `with` の実行中に呼び出された場合は、`with` が終わるまで削除を延期する。
+/// `live_resources` の数は、実際に削除された時点で減る。
+pub(crate) fn delete_resource<F>(id: ContextId, generation: u64, kind: ResourceKind, delete: F)
+where
+    F: FnOnce(&Context) + 'static,
+{
+    run_deletion(Deletion {
+        context: id,
+        generation,
+        kind,
+        delete: Box::new(delete),
+    });
+}
+
+fn run_deletion(deletion: Deletion) {
+    if let Some(ctx) = get(deletion.context) {
+        let ctx = match ctx.try_borrow() {
+            Ok(ctx) => ctx,
+            Err(_) => {
+                let _ = DEFERRED_DELETIONS.try_with(|d| d.borrow_mut().push(deletion));
+                return;
+            }
+        };
+        if ctx.generation == deletion.generation {
+            (deletion.delete)(&ctx);
+        }
+    }
+    resource::count_dropped(deletion.kind);
+}
+
+fn run_deferred_deletions() {
+    let deletions = DEFERRED_DELETIONS
+        .try_with(|d| std::mem::take(&mut *d.borrow_mut()))
+        .unwrap_or_default();
+    // まだ `with` の実行中のContextのものは、再び延期される
+    for deletion in deletions {
+        run_deletion(deletion);
+    }
 }
 
 impl Context {
+    pub fn id(&self) -> ContextId {
+        self.id
+    }
+
+    /// このContextを生成したcanvas
+    pub fn canvas(&self) -> &web_sys::HtmlCanvasElement {
+        &self.canvas
+    }
+
+    /// このContextで使える機能
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// WebGL2のContextの場合、WebGL2でのみ使えるAPIを呼び出すために使う
+    pub fn gl2(&self) -> Option<&web_sys::WebGl2RenderingContext> {
+        self.gl2.as_ref()
+    }
+
+    /// Contextの世代
+    /// Contextが作り直される、または失われたContextが復元されるたびに増加する。
+    /// GPU側の状態をキャッシュする場合、世代が変わったらキャッシュを破棄する必要がある。
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Contextが失われているかどうか
+    /// 失われている間は描画やリソースの生成を行っても何も起こらない。
+    /// 復元されると `generation` が変わる。
+    pub fn is_lost(&self) -> bool {
+        self.gl.is_context_lost()
+    }
+
+    /// このContextが生成されてから、VBOやIBOにデータを転送した回数
+    pub fn buffer_uploads(&self) -> usize {
+        self.buffer_uploads
+    }
+
+    pub(crate) fn count_buffer_upload(&mut self) {
+        self.buffer_uploads += 1;
+    }
+
+    /// 復元されたContextは全てのGPUリソースを失っているため、世代を進める
+    /// 各リソースは、次に使われるときに世代の変化を検知して作り直される
+    pub(crate) fn on_restored(&mut self) {
+        self.generation = next_generation();
+        self.enabled_vertex_attrib_locations.clear();
+    }
+
     pub fn clear_color_and_depth(&self, color: (f32, f32, f32, f32), depth: f32) {
         let (r, g, b, a) = color;
         self.gl.clear_color(r, g, b, a);
@@ -55,6 +422,17 @@ impl Context {
         self.gl.depth_func(depth_func.to_gl());
     }
 
+    /// 深度バッファへの書き込みを有効化する
+    pub fn enable_depth_write(&self) {
+        self.gl.depth_mask(true);
+    }
+
+    /// 深度バッファへの書き込みを無効化する
+    /// 深度テストは引き続き行われる
+    pub fn disable_depth_write(&self) {
+        self.gl.depth_mask(false);
+    }
+
     /// ブレンディングを有効化する
     ///
     /// ## ブレンディングの計算式
@@ -65,6 +443,11 @@ impl Context {
         self.gl.blend_func(src_fac.to_gl(), dst_fac.to_gl());
     }
 
+    /// ブレンディングを無効化する
+    pub fn disable_blending(&self) {
+        self.gl.disable(GL::BLEND);
+    }
+
     /// 分割ブレンディングを有効化する
     ///
     /// ## ブレンディングの計算式
@@ -230,3 +613,90 @@ impl BlendFactor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{buffers::VBO, test_util, texture::GlTexture};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_one_context_per_canvas() {
+        let canvas = test_util::canvas();
+        let id = create(&canvas).unwrap();
+        assert_eq!(create(&canvas).unwrap(), id);
+
+        let other = create(&test_util::canvas()).unwrap();
+        assert_ne!(other, id);
+
+        destroy(id);
+        destroy(other);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_initialize_again() {
+        let canvas = test_util::canvas();
+        initialize(&canvas).unwrap();
+        let id = current().unwrap();
+
+        // 同じcanvasであれば、同じContextを使い続ける
+        initialize(&canvas).unwrap();
+        assert_eq!(current(), Some(id));
+
+        // 別のcanvasであれば、以前のContextは破棄される
+        initialize(&test_util::canvas()).unwrap();
+        assert_ne!(current(), Some(id));
+        assert!(get(id).is_none());
+
+        destroy(current().unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_initialize_keeps_created_context() {
+        let created = create(&test_util::canvas()).unwrap();
+        make_current(created);
+
+        // `create` で生成したContextは、初期化し直しても破棄されない
+        initialize(&test_util::canvas()).unwrap();
+        let initialized = current().unwrap();
+        initialize(&test_util::canvas()).unwrap();
+        assert!(get(created).is_some());
+        assert!(get(initialized).is_none());
+
+        destroy(created);
+        destroy(current().unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_try_with_destroyed_context() {
+        let id = create(&test_util::canvas()).unwrap();
+        assert!(try_with_id(id, |ctx| ctx.id()).is_ok());
+
+        destroy(id);
+        assert_eq!(
+            try_with_id(id, |ctx| ctx.id()),
+            Err(Error::ContextUnavailable(None))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_try_with_uninitialized() {
+        let prev = current();
+        CURRENT_CONTEXT.with(|current| current.set(None));
+
+        assert_eq!(
+            try_with(|ctx| ctx.id()),
+            Err(Error::ContextUnavailable(None))
+        );
+        assert_eq!(
+            VBO::<Vec<f32>>::new().err(),
+            Some(Error::ContextUnavailable(None))
+        );
+        assert_eq!(
+            GlTexture::new().err(),
+            Some(Error::ContextUnavailable(None))
+        );
+
+        CURRENT_CONTEXT.with(|current| current.set(prev));
+    }
+}
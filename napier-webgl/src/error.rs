@@ -0,0 +1,117 @@
+use super::{capabilities::Backend, shader::ShaderStage};
+use std::fmt;
+use wasm_bindgen::JsValue;
+
+/// napier-webglの各操作で発生するエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// canvasから指定したバージョンのWebGLのContextを取得できない
+    /// `None` の場合は、操作対象のContextが初期化されていない、または既に破棄されている
+    ContextUnavailable(Option<Backend>),
+    /// shaderのコンパイルに失敗した
+    ShaderCompile {
+        /// コンパイルに失敗したshaderの種類
+        stage: ShaderStage,
+        /// コンパイラが出力したログ
+        log: String,
+    },
+    /// programのlinkに失敗した
+    Link {
+        /// linkerが出力したログ
+        log: String,
+    },
+    /// shaderに指定した名前のattribute変数が無い
+    MissingAttribute(&'static str),
+    /// shaderに指定した名前のuniform変数が無い
+    /// 宣言されていても、使われていない変数はコンパイル時に削除される
+    MissingUniform(&'static str),
+    /// GPUリソースの生成に失敗した。値は生成しようとしたリソースの種類
+    ResourceCreation(&'static str),
+    /// 別のContextで生成したGPUリソースを使おうとした
+    ContextMismatch,
+    /// このContextでは扱えないテクスチャのサイズ
+    InvalidTextureSize { width: i32, height: i32 },
+    /// フレームバッファが描画先として使えない状態にある
+    IncompleteFramebuffer {
+        /// `checkFramebufferStatus` が返した値
+        status: u32,
+    },
+    /// WebGLのAPIが投げた例外
+    Js(JsValue),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ContextUnavailable(Some(Backend::WebGl1)) => {
+                write!(f, "WebGL is not supported")
+            }
+            Error::ContextUnavailable(Some(Backend::WebGl2)) => {
+                write!(f, "WebGL2 is not supported")
+            }
+            Error::ContextUnavailable(None) => write!(f, "WebGL context is not available"),
+            Error::ShaderCompile { stage, log } => {
+                write!(f, "failed to compile {} shader: {}", stage, log)
+            }
+            Error::Link { log } => write!(f, "failed to link program: {}", log),
+            Error::MissingAttribute(name) => write!(f, "missing attribute \"{}\"", name),
+            Error::MissingUniform(name) => write!(f, "missing uniform \"{}\"", name),
+            Error::ResourceCreation(kind) => write!(f, "failed to create {}", kind),
+            Error::ContextMismatch => write!(f, "resource belongs to another context"),
+            Error::InvalidTextureSize { width, height } => {
+                write!(f, "invalid texture size: {}x{}", width, height)
+            }
+            Error::IncompleteFramebuffer { status } => {
+                write!(f, "incomplete framebuffer (status: {:#x})", status)
+            }
+            Error::Js(value) => match value.as_string() {
+                Some(msg) => write!(f, "{}", msg),
+                None => write!(f, "{:?}", value),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Error {
+        Error::Js(value)
+    }
+}
+
+/// `Result<_, JsValue>` を返す関数の中で `?` を使えるようにする
+impl From<Error> for JsValue {
+    fn from(err: Error) -> JsValue {
+        match err {
+            Error::Js(value) => value,
+            err => JsValue::from_str(err.to_string().as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_display() {
+        let err = Error::ShaderCompile {
+            stage: ShaderStage::Fragment,
+            log: "ERROR: 0:1: syntax error".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "failed to compile fragment shader: ERROR: 0:1: syntax error"
+        );
+        assert_eq!(
+            Error::ContextUnavailable(None).to_string(),
+            "WebGL context is not available"
+        );
+        assert_eq!(
+            Error::MissingUniform("mvpMatrix").to_string(),
+            "missing uniform \"mvpMatrix\""
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use super::{
+    context::{self, ContextId},
+    error::Error,
+    resource::{self, ResourceKind},
+    texture::{GlTexture, MagMethod, MinMethod, WrapMethod},
+};
+use web_sys::WebGlRenderingContext as GL;
+
+/// オフスクリーン描画のためのフレームバッファ
+/// 色はテクスチャに、深度はレンダーバッファに書き込まれる
+/// Contextが復元された場合は、作り直す必要がある
+#[derive(Debug)]
+pub struct GlFramebuffer {
+    framebuffer: web_sys::WebGlFramebuffer,
+    depth_buffer: web_sys::WebGlRenderbuffer,
+    texture: GlTexture,
+    width: i32,
+    height: i32,
+    // framebufferを生成したContext
+    context: ContextId,
+    // framebufferを生成したContextの世代
+    generation: u64,
+}
+
+impl GlFramebuffer {
+    pub fn new(width: i32, height: i32) -> Result<GlFramebuffer, Error> {
+        // 色の書き込み先となるテクスチャ
+        // 画像サイズが2の冪乗とは限らないため、ミップマップを使わずクランプする
+        let texture = GlTexture::new()?;
+        texture.bind()?;
+        texture.allocate(width, height)?;
+        texture.set_minify_filter(MinMethod::Nearest);
+        texture.set_magnify_filter(MagMethod::Nearest);
+        texture.set_wrap_s(WrapMethod::ClampToEdge);
+        texture.set_wrap_t(WrapMethod::ClampToEdge);
+        texture.unbind();
+
+        // 失敗時にテクスチャをDropできるよう、GlFramebufferの構築はcontextの外で行う
+        let (framebuffer, depth_buffer, context, generation) = context::try_with(|ctx| {
+            // 深度の書き込み先となるレンダーバッファ
+            let depth_buffer = ctx
+                .create_renderbuffer()
+                .ok_or(Error::ResourceCreation("renderbuffer"))?;
+            ctx.bind_renderbuffer(GL::RENDERBUFFER, Some(&depth_buffer));
+            ctx.renderbuffer_storage(GL::RENDERBUFFER, GL::DEPTH_COMPONENT16, width, height);
+            ctx.bind_renderbuffer(GL::RENDERBUFFER, None);
+
+            let framebuffer = match ctx.create_framebuffer() {
+                Some(framebuffer) => framebuffer,
+                None => {
+                    ctx.delete_renderbuffer(Some(&depth_buffer));
+                    return Err(Error::ResourceCreation("framebuffer"));
+                }
+            };
+            ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+            ctx.framebuffer_texture_2d(
+                GL::FRAMEBUFFER,
+                GL::COLOR_ATTACHMENT0,
+                GL::TEXTURE_2D,
+                texture.gl_texture().as_ref(),
+                0,
+            );
+            ctx.framebuffer_renderbuffer(
+                GL::FRAMEBUFFER,
+                GL::DEPTH_ATTACHMENT,
+                GL::RENDERBUFFER,
+                Some(&depth_buffer),
+            );
+
+            let status = ctx.check_framebuffer_status(GL::FRAMEBUFFER);
+            ctx.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+            if status != GL::FRAMEBUFFER_COMPLETE {
+                ctx.delete_framebuffer(Some(&framebuffer));
+                ctx.delete_renderbuffer(Some(&depth_buffer));
+                return Err(Error::IncompleteFramebuffer { status });
+            }
+
+            Ok((framebuffer, depth_buffer, ctx.id(), ctx.generation()))
+        })??;
+        resource::count_created(ResourceKind::Framebuffer);
+
+        Ok(GlFramebuffer {
+            framebuffer,
+            depth_buffer,
+            texture,
+            width,
+            height,
+            context,
+            generation,
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// 色の書き込み先となっているテクスチャ
+    pub fn texture(&self) -> &GlTexture {
+        &self.texture
+    }
+
+    /// 以降の描画先をこのフレームバッファにする
+    pub fn bind(&self) {
+        context::with_id(self.context, |ctx| {
+            ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.framebuffer))
+        })
+    }
+
+    /// 以降の描画先をcanvasに戻す
+    pub fn unbind(&self) {
+        context::with_id(self.context, |ctx| {
+            ctx.bind_framebuffer(GL::FRAMEBUFFER, None)
+        })
+    }
+
+    /// 指定された位置のピクセルのRGBA値を読み出す
+    /// 座標は左下を原点とする
+    /// この関数を呼び出す前に、bindを実行しておく必要がある
+    pub fn read_pixel(&self, x: i32, y: i32) -> Result<[u8; 4], Error> {
+        let mut pixel = [0; 4];
+        context::with_id(self.context, |ctx| {
+            ctx.read_pixels_with_opt_u8_array(
+                x,
+                y,
+                1,
+                1,
+                GL::RGBA,
+                GL::UNSIGNED_BYTE,
+                Some(&mut pixel[..]),
+            )
+        })?;
+        Ok(pixel)
+    }
+}
+
+impl Drop for GlFramebuffer {
+    fn drop(&mut self) {
+        // テクスチャは `GlTexture` のDropで削除される
+        let (framebuffer, depth_buffer) = (self.framebuffer.clone(), self.depth_buffer.clone());
+        context::delete_resource(
+            self.context,
+            self.generation,
+            ResourceKind::Framebuffer,
+            move |ctx| {
+                ctx.delete_framebuffer(Some(&framebuffer));
+                ctx.delete_renderbuffer(Some(&depth_buffer));
+            },
+        );
+    }
+}
@@ -1,8 +1,34 @@
 pub mod buffers;
+pub mod capabilities;
 pub mod context;
+pub mod error;
+pub mod framebuffer;
 pub mod program;
+pub mod resource;
 pub mod shader;
 pub mod texture;
 pub mod vec;
 
+pub use error::Error;
 pub use web_sys::WebGlRenderingContext as GL;
+
+// WebGLのContextを使うテストがあるため、ブラウザ上で実行する
+#[cfg(test)]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use wasm_bindgen::JsCast as _;
+
+    /// テスト用に、documentに追加していないcanvasを生成する
+    pub fn canvas() -> web_sys::HtmlCanvasElement {
+        web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into()
+            .unwrap()
+    }
+}
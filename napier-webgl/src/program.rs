@@ -1,13 +1,14 @@
 use super::{
     buffers::VBO,
-    context::{self, Context},
+    context::{self, Context, ContextId},
+    error::Error,
+    resource::{self, ResourceKind},
     shader::{FragmentShader, VertexShader},
     texture::GlTextureUnit,
     vec::StepVec,
 };
-use cgmath::{Array, Matrix4, Vector3, Vector4};
+use cgmath::{Array, Matrix3, Matrix4, Vector2, Vector3, Vector4};
 use std::marker::PhantomData;
-use wasm_bindgen::JsValue;
 use web_sys::WebGlRenderingContext as GL;
 
 #[allow(dead_code)]
@@ -17,6 +18,10 @@ pub struct GlProgram<P> {
     frag_shader: FragmentShader,
     pub params: P,
     vertex_attrib_locations: Vec<u32>,
+    // programを生成したContext
+    context: ContextId,
+    // programを生成したContextの世代
+    generation: u64,
 }
 
 impl<P> GlProgram<P>
@@ -26,43 +31,94 @@ where
     pub fn new(
         vert_shader: VertexShader,
         frag_shader: FragmentShader,
-    ) -> Result<GlProgram<P>, JsValue> {
-        context::with(|ctx| {
-            let program = ctx.create_program().unwrap();
+    ) -> Result<GlProgram<P>, Error> {
+        // link失敗時にshaderをDropできるよう、GlProgramの構築はcontextの外で行う
+        let (program, params, vertex_attrib_locations, context, generation) =
+            context::try_with(|ctx| {
+                let program = ctx
+                    .create_program()
+                    .ok_or(Error::ResourceCreation("program"))?;
+
+                // 作成したprogramを各shaderを関連づける
+                ctx.attach_shader(&program, &vert_shader.shader);
+                ctx.attach_shader(&program, &frag_shader.shader);
+
+                match link(ctx, &program) {
+                    Ok((params, locations)) => {
+                        Ok((program, params, locations, ctx.id(), ctx.generation()))
+                    }
+                    Err(e) => {
+                        ctx.delete_program(Some(&program));
+                        Err(e)
+                    }
+                }
+            })??;
+        resource::count_created(ResourceKind::Program);
+
+        Ok(GlProgram {
+            program,
+            vert_shader,
+            frag_shader,
+            params,
+            vertex_attrib_locations,
+            context,
+            generation,
+        })
+    }
+}
 
-            // 作成したprogramを各shaderを関連づける
-            ctx.attach_shader(&program, &vert_shader.shader);
-            ctx.attach_shader(&program, &frag_shader.shader);
+impl<P> GlProgram<P>
+where
+    P: ParamsBase,
+{
+    /// programをlinkし直す。
+    /// uniform変数の位置はlinkのたびに変わるため、`params` も作り直される。
+    /// そのため、各uniform変数にキャッシュされていた値は破棄される。
+    pub fn relink(&mut self) -> Result<(), Error> {
+        let (params, vertex_attrib_locations) =
+            context::with_id(self.context, |ctx| link(ctx, &self.program))?;
+        self.params = params;
+        self.vertex_attrib_locations = vertex_attrib_locations;
+        Ok(())
+    }
+}
 
-            // contextにprogramをlinkする (両shaderをlinkする)
-            // 両shaderに対するGPUコードの準備を完了させる
-            ctx.link_program(&program);
+impl<P> Drop for GlProgram<P> {
+    fn drop(&mut self) {
+        // shaderはこの後、それぞれのDropで削除される
+        let program = self.program.clone();
+        context::delete_resource(
+            self.context,
+            self.generation,
+            ResourceKind::Program,
+            move |ctx| ctx.delete_program(Some(&program)),
+        );
+    }
+}
 
-            let success_link = ctx
-                .get_program_parameter(&program, GL::LINK_STATUS)
-                .as_bool()
-                .unwrap();
+/// contextにprogramをlinkし (両shaderをlinkする)、各変数の位置を取得する
+fn link<P>(ctx: &Context, program: &web_sys::WebGlProgram) -> Result<(P, Vec<u32>), Error>
+where
+    P: ParamsBase,
+{
+    // 両shaderに対するGPUコードの準備を完了させる
+    ctx.link_program(program);
 
-            if !success_link {
-                let err_msg = ctx.get_program_info_log(&program).unwrap();
-                return Err(JsValue::from_str(err_msg.as_str()));
-            }
+    let success_link = ctx
+        .get_program_parameter(program, GL::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false);
 
-            let mut visitor = ParamsVisitor::new(ctx, &program);
+    if !success_link {
+        let log = ctx.get_program_info_log(program).unwrap_or_default();
+        return Err(Error::Link { log });
+    }
 
-            let params = P::from_visitor(&mut visitor)?;
+    let mut visitor = ParamsVisitor::new(ctx, program);
 
-            let vertex_attrib_locations = visitor.vertex_attrib_locations;
+    let params = P::from_visitor(&mut visitor)?;
 
-            Ok(GlProgram {
-                program,
-                vert_shader,
-                frag_shader,
-                params,
-                vertex_attrib_locations,
-            })
-        })
-    }
+    Ok((params, visitor.vertex_attrib_locations))
 }
 
 impl<P> GlProgram<P> {
@@ -77,7 +133,7 @@ impl<P> GlProgram<P> {
  * ========
  */
 pub trait ParamsBase {
-    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, JsValue>
+    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, Error>
     where
         Self: Sized;
 }
@@ -97,7 +153,7 @@ impl<'a> ParamsVisitor<'a> {
         }
     }
 
-    pub fn visit_attr<A>(&mut self, name: &'static str) -> Result<Attribute<StepVec<A>>, JsValue>
+    pub fn visit_attr<A>(&mut self, name: &'static str) -> Result<Attribute<StepVec<A>>, Error>
     where
         A: Array<Element = f32>,
     {
@@ -105,8 +161,7 @@ impl<'a> ParamsVisitor<'a> {
         let loc = self.ctx.get_attrib_location(self.program, name);
 
         if loc < 0 {
-            let msg = format!("missing attribute \"{}\"", name);
-            return Err(JsValue::from_str(msg.as_str()));
+            return Err(Error::MissingAttribute(name));
         }
         let loc = loc as u32;
 
@@ -115,12 +170,11 @@ impl<'a> ParamsVisitor<'a> {
         Ok(Attribute::new(name, loc))
     }
 
-    pub fn visit_uniform<T>(&self, name: &'static str) -> Result<Uniform<T>, JsValue> {
+    pub fn visit_uniform<T>(&self, name: &'static str) -> Result<Uniform<T>, Error> {
         if let Some(loc) = self.ctx.get_uniform_location(self.program, name) {
             Ok(Uniform::new(name, loc))
         } else {
-            let msg = format!("missing uniform \"{}\"", name);
-            Err(JsValue::from_str(msg.as_str()))
+            Err(Error::MissingUniform(name))
         }
     }
 }
@@ -149,8 +203,14 @@ where
         }
     }
 
-    pub fn attach_vbo(&self, vbo: &VBO<StepVec<A>>) {
-        vbo.bind();
+    /// attributeは操作対象のContextに設定するため、
+    /// `vbo` が別のContextで生成されている場合は `Error::ContextMismatch` を返す
+    pub fn attach_vbo(&self, vbo: &VBO<StepVec<A>>) -> Result<(), Error> {
+        let current = context::current().ok_or(Error::ContextUnavailable(None))?;
+        if vbo.context() != current {
+            return Err(Error::ContextMismatch);
+        }
+        vbo.bind()?;
 
         context::with(|ctx| {
             ctx.vertex_attrib_pointer_with_i32(
@@ -164,6 +224,7 @@ where
         });
 
         vbo.unbind();
+        Ok(())
     }
 }
 
@@ -176,7 +237,11 @@ pub struct Uniform<V> {
     #[allow(dead_code)]
     pub name: &'static str,
     pub location: web_sys::WebGlUniformLocation,
+    /// 最後に送信した値
     pub value: Option<V>,
+    /// `value` を送信したときのContextの世代
+    /// Contextが作り直されるとuniform変数の値も失われるため、世代が変わったら送信し直す
+    context_generation: u64,
 }
 
 impl<V> Uniform<V> {
@@ -185,53 +250,137 @@ impl<V> Uniform<V> {
             name,
             location,
             value: None,
+            context_generation: 0,
         }
     }
 }
 
+impl<V> Uniform<V>
+where
+    V: PartialEq,
+{
+    /// 最後に送信した値と異なる場合のみ、`upload` で値を送信する
+    /// uniform変数の値は使用中のprogramに送信されるため、操作対象のContextを使う
+    /// 操作対象のContextが無い場合は `Error::ContextUnavailable` を返す
+    fn update<F>(&mut self, value: V, upload: F) -> Result<(), Error>
+    where
+        F: FnOnce(&Context, &web_sys::WebGlUniformLocation, &V),
+    {
+        context::try_with(|ctx| {
+            let generation = ctx.generation();
+            if self.context_generation == generation && self.value.as_ref() == Some(&value) {
+                return;
+            }
+
+            upload(ctx, &self.location, &value);
+            self.value = Some(value);
+            self.context_generation = generation;
+        })
+    }
+}
+
+impl Uniform<Matrix3<f32>> {
+    pub fn set_value(&mut self, value: Matrix3<f32>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform_matrix3fv_with_f32_array(
+                Some(loc),
+                false,
+                &AsRef::<[f32; 9]>::as_ref(value)[..],
+            )
+        })
+    }
+}
+
 impl Uniform<Matrix4<f32>> {
-    pub fn set_value(&mut self, value: Matrix4<f32>) {
-        context::with(|ctx| {
+    pub fn set_value(&mut self, value: Matrix4<f32>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
             ctx.uniform_matrix4fv_with_f32_array(
-                Some(&self.location),
+                Some(loc),
                 false,
-                &AsRef::<[f32; 16]>::as_ref(&value)[..],
+                &AsRef::<[f32; 16]>::as_ref(value)[..],
             )
-        });
+        })
+    }
+}
 
-        self.value = Some(value);
+impl Uniform<f32> {
+    pub fn set_value(&mut self, value: f32) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| ctx.uniform1f(Some(loc), *value))
     }
 }
 
 impl Uniform<Vector3<f32>> {
-    pub fn set_value(&mut self, value: Vector3<f32>) {
-        context::with(|ctx| ctx.uniform3f(Some(&self.location), value.x, value.y, value.z));
-
-        self.value = Some(value);
+    pub fn set_value(&mut self, value: Vector3<f32>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform3f(Some(loc), value.x, value.y, value.z)
+        })
     }
 }
 
 impl Uniform<Vector4<f32>> {
-    pub fn set_value(&mut self, value: Vector4<f32>) {
-        context::with(|ctx| {
-            ctx.uniform4f(Some(&self.location), value.x, value.y, value.z, value.w)
-        });
+    pub fn set_value(&mut self, value: Vector4<f32>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform4f(Some(loc), value.x, value.y, value.z, value.w)
+        })
+    }
+}
+
+impl Uniform<Vec<Vector2<f32>>> {
+    /// uniform変数の配列に値を設定する
+    /// `value` の長さは配列の長さ以下でなければならない
+    pub fn set_value(&mut self, value: Vec<Vector2<f32>>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            let array = value
+                .iter()
+                .flat_map(|v| AsRef::<[f32; 2]>::as_ref(v).iter().copied())
+                .collect::<Vec<_>>();
+            ctx.uniform2fv_with_f32_array(Some(loc), &array)
+        })
+    }
+}
 
-        self.value = Some(value);
+impl Uniform<Vec<Vector3<f32>>> {
+    /// uniform変数の配列に値を設定する
+    /// `value` の長さは配列の長さ以下でなければならない
+    pub fn set_value(&mut self, value: Vec<Vector3<f32>>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            let array = value
+                .iter()
+                .flat_map(|v| AsRef::<[f32; 3]>::as_ref(v).iter().copied())
+                .collect::<Vec<_>>();
+            ctx.uniform3fv_with_f32_array(Some(loc), &array)
+        })
     }
 }
 
 impl Uniform<i32> {
-    pub fn set_value(&mut self, value: i32) {
-        context::with(|ctx| ctx.uniform1i(Some(&self.location), value));
-        self.value = Some(value);
+    pub fn set_value(&mut self, value: i32) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| ctx.uniform1i(Some(loc), *value))
+    }
+}
+
+impl Uniform<bool> {
+    pub fn set_value(&mut self, value: bool) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform1i(Some(loc), *value as i32)
+        })
+    }
+}
+
+impl Uniform<Vec<i32>> {
+    /// uniform変数の配列に値を設定する
+    /// `value` の長さは配列の長さ以下でなければならない
+    pub fn set_value(&mut self, value: Vec<i32>) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform1iv_with_i32_array(Some(loc), value)
+        })
     }
 }
 
 impl Uniform<GlTextureUnit> {
-    pub fn set_value(&mut self, value: GlTextureUnit) {
-        let i = value.to_int();
-        context::with(|ctx| ctx.uniform1i(Some(&self.location), i));
-        self.value = Some(value);
+    pub fn set_value(&mut self, value: GlTextureUnit) -> Result<(), Error> {
+        self.update(value, |ctx, loc, value| {
+            ctx.uniform1i(Some(loc), value.to_int())
+        })
     }
 }
@@ -0,0 +1,124 @@
+use std::cell::Cell;
+
+/// 生存している（まだDropされていない）GPUリソースの数
+///
+/// リソースのリークを検出するためのもので、テストなどで
+/// 処理の前後の `live_resources()` を比較して使うことを想定している。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LiveResources {
+    /// VBO, IBO
+    pub buffers: usize,
+    /// GlTexture
+    pub textures: usize,
+    /// GlProgram
+    pub programs: usize,
+    /// VertexShader, FragmentShader
+    pub shaders: usize,
+    /// GlFramebuffer
+    pub framebuffers: usize,
+}
+
+impl LiveResources {
+    pub fn total(&self) -> usize {
+        self.buffers + self.textures + self.programs + self.shaders + self.framebuffers
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResourceKind {
+    Buffer,
+    Texture,
+    Program,
+    Shader,
+    Framebuffer,
+}
+
+thread_local! {
+    static LIVE_RESOURCES: Cell<LiveResources> = Cell::new(LiveResources::default());
+}
+
+/// 現在生存しているGPUリソースの数を返す
+pub fn live_resources() -> LiveResources {
+    LIVE_RESOURCES.with(Cell::get)
+}
+
+pub(crate) fn count_created(kind: ResourceKind) {
+    update(kind, |n| n + 1);
+}
+
+pub(crate) fn count_dropped(kind: ResourceKind) {
+    update(kind, |n| n - 1);
+}
+
+fn update<F>(kind: ResourceKind, func: F)
+where
+    F: FnOnce(usize) -> usize,
+{
+    // スレッドの終了時にDropされた場合は、既にカウンタが破棄されていることがある
+    let _ = LIVE_RESOURCES.try_with(|cell| {
+        let mut live = cell.get();
+        let n = match kind {
+            ResourceKind::Buffer => &mut live.buffers,
+            ResourceKind::Texture => &mut live.textures,
+            ResourceKind::Program => &mut live.programs,
+            ResourceKind::Shader => &mut live.shaders,
+            ResourceKind::Framebuffer => &mut live.framebuffers,
+        };
+        *n = func(*n);
+        cell.set(live);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buffers::VBO,
+        context,
+        error::Error,
+        program::{GlProgram, ParamsBase, ParamsVisitor},
+        shader::{FragmentShader, VertexShader},
+        test_util,
+        texture::GlTexture,
+    };
+    use wasm_bindgen_test::*;
+
+    struct EmptyParams;
+
+    impl ParamsBase for EmptyParams {
+        fn from_visitor(_: &mut ParamsVisitor) -> Result<Self, Error> {
+            Ok(EmptyParams)
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_live_resources() {
+        let id = context::create(&test_util::canvas()).unwrap();
+        context::make_current(id);
+        let before = live_resources();
+
+        let vbo = VBO::<Vec<f32>>::with_data(&vec![0.0, 1.0, 2.0]).unwrap();
+        let texture = GlTexture::new().unwrap();
+        let vert = VertexShader::compile("void main() { gl_Position = vec4(0.0); }").unwrap();
+        let frag = FragmentShader::compile(
+            "precision mediump float; void main() { gl_FragColor = vec4(1.0); }",
+        )
+        .unwrap();
+        let program = GlProgram::<EmptyParams>::new(vert, frag).unwrap();
+        assert_eq!(live_resources().buffers, before.buffers + 1);
+        assert_eq!(live_resources().total(), before.total() + 5);
+
+        drop(program);
+        drop(texture);
+        assert_eq!(live_resources().buffers, before.buffers + 1);
+
+        // `with` の実行中にDropされた場合は、`with` が終わってから削除される
+        context::with(|_| {
+            drop(vbo);
+            assert_eq!(live_resources().buffers, before.buffers + 1);
+        });
+        assert_eq!(live_resources(), before);
+
+        context::destroy(id);
+    }
+}
@@ -1,36 +1,107 @@
-use super::context::{self, Context};
-use wasm_bindgen::JsValue;
+use super::{
+    context::{self, Context, ContextId},
+    error::Error,
+    resource::{self, ResourceKind},
+};
+use std::fmt;
 use web_sys::WebGlRenderingContext as GL;
 
+/// shaderの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn to_gl(&self) -> u32 {
+        match self {
+            ShaderStage::Vertex => GL::VERTEX_SHADER,
+            ShaderStage::Fragment => GL::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
 pub struct VertexShader {
     pub shader: web_sys::WebGlShader,
+    // shaderを生成したContext
+    context: ContextId,
+    // shaderを生成したContextの世代
+    generation: u64,
 }
 
 impl VertexShader {
-    pub fn compile(src: &str) -> Result<Self, JsValue> {
-        context::with(|ctx| {
-            let shader = compile(ctx, src, GL::VERTEX_SHADER)?;
-            Ok(VertexShader { shader })
-        })
+    pub fn compile(src: &str) -> Result<Self, Error> {
+        context::try_with(|ctx| {
+            let shader = compile(ctx, src, ShaderStage::Vertex)?;
+            Ok(VertexShader {
+                shader,
+                context: ctx.id(),
+                generation: ctx.generation(),
+            })
+        })?
+    }
+}
+
+impl Drop for VertexShader {
+    fn drop(&mut self) {
+        let shader = self.shader.clone();
+        context::delete_resource(
+            self.context,
+            self.generation,
+            ResourceKind::Shader,
+            move |ctx| ctx.delete_shader(Some(&shader)),
+        );
     }
 }
 
 pub struct FragmentShader {
     pub shader: web_sys::WebGlShader,
+    // shaderを生成したContext
+    context: ContextId,
+    // shaderを生成したContextの世代
+    generation: u64,
 }
 
 impl FragmentShader {
-    pub fn compile(src: &str) -> Result<Self, JsValue> {
-        context::with(|ctx| {
-            let shader = compile(ctx, src, GL::FRAGMENT_SHADER)?;
-            Ok(FragmentShader { shader })
-        })
+    pub fn compile(src: &str) -> Result<Self, Error> {
+        context::try_with(|ctx| {
+            let shader = compile(ctx, src, ShaderStage::Fragment)?;
+            Ok(FragmentShader {
+                shader,
+                context: ctx.id(),
+                generation: ctx.generation(),
+            })
+        })?
+    }
+}
+
+impl Drop for FragmentShader {
+    fn drop(&mut self) {
+        let shader = self.shader.clone();
+        context::delete_resource(
+            self.context,
+            self.generation,
+            ResourceKind::Shader,
+            move |ctx| ctx.delete_shader(Some(&shader)),
+        );
     }
 }
 
-fn compile(ctx: &Context, src: &str, shader_type: u32) -> Result<web_sys::WebGlShader, JsValue> {
+fn compile(ctx: &Context, src: &str, stage: ShaderStage) -> Result<web_sys::WebGlShader, Error> {
     // shaderオブジェクトの作成
-    let shader = ctx.create_shader(shader_type).unwrap();
+    let shader = ctx
+        .create_shader(stage.to_gl())
+        .ok_or(Error::ResourceCreation("shader"))?;
 
     // shaderにソースコードを渡す
     ctx.shader_source(&shader, src);
@@ -44,9 +115,11 @@ fn compile(ctx: &Context, src: &str, shader_type: u32) -> Result<web_sys::WebGlS
         .as_bool()
         .unwrap_or(false)
     {
+        resource::count_created(ResourceKind::Shader);
         Ok(shader)
     } else {
-        let err_msg = ctx.get_shader_info_log(&shader).unwrap();
-        Err(JsValue::from_str(err_msg.as_str()))
+        let log = ctx.get_shader_info_log(&shader).unwrap_or_default();
+        ctx.delete_shader(Some(&shader));
+        Err(Error::ShaderCompile { stage, log })
     }
 }
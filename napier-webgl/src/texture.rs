@@ -1,90 +1,244 @@
-use super::context;
-use wasm_bindgen::JsValue;
+use super::{
+    context::{self, Context, ContextId},
+    error::Error,
+    resource::{self, ResourceKind},
+};
+use std::cell::{Cell, RefCell};
 use web_sys::WebGlRenderingContext as GL;
 
+/// テクスチャオブジェクト
+/// 設定した画像やパラメータはCPU側にも保持し、Contextが復元されたときに設定し直す
 #[derive(Debug, PartialEq)]
 pub struct GlTexture {
-    gl_texture: web_sys::WebGlTexture,
+    // textureを生成したContext
+    context: ContextId,
+    // Contextが失われている間に生成された場合は `None`
+    gl_texture: RefCell<Option<web_sys::WebGlTexture>>,
+    // textureを生成したContextの世代
+    generation: Cell<u64>,
+    recipe: RefCell<TextureRecipe>,
+}
+
+/// Contextの復元時にテクスチャを作り直すための情報
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TextureRecipe {
+    /// 画素データ。`allocate` で確保した場合は `None`
+    pixels: Option<Vec<u8>>,
+    size: Option<(i32, i32)>,
+    mipmap: bool,
+    /// (パラメータ名, 値) の組
+    parameters: Vec<(u32, u32)>,
 }
 
 impl GlTexture {
-    pub fn new() -> GlTexture {
-        context::with(|ctx| GlTexture {
-            gl_texture: ctx.create_texture().unwrap(),
-        })
+    /// Contextが失われている間は、テクスチャを生成せずに `None` を保持する
+    /// その場合は、Contextが復元されたときに生成される
+    pub fn new() -> Result<GlTexture, Error> {
+        let texture = context::try_with(|ctx| {
+            let gl_texture = ctx.create_texture();
+            if gl_texture.is_none() && !ctx.is_lost() {
+                return Err(Error::ResourceCreation("texture"));
+            }
+            Ok(GlTexture {
+                context: ctx.id(),
+                gl_texture: RefCell::new(gl_texture),
+                generation: Cell::new(ctx.generation()),
+                recipe: RefCell::new(TextureRecipe::default()),
+            })
+        })??;
+        resource::count_created(ResourceKind::Texture);
+        Ok(texture)
+    }
+
+    /// textureを生成したContext
+    pub fn context(&self) -> ContextId {
+        self.context
     }
 
-    pub fn bind(&self) {
-        context::with(|ctx| ctx.bind_texture(GL::TEXTURE_2D, Some(&self.gl_texture)))
+    /// Contextが復元されていれば、テクスチャを作り直してから `bind` する
+    /// Contextが既に破棄されている場合は `Error::ContextUnavailable` を返す
+    pub fn bind(&self) -> Result<(), Error> {
+        context::try_with_id(self.context, |ctx| {
+            if self.generation.get() != ctx.generation() {
+                self.restore(ctx);
+            }
+            ctx.bind_texture(GL::TEXTURE_2D, self.gl_texture.borrow().as_ref())
+        })
     }
 
+    /// Contextが既に破棄されている場合は何もしない
     pub fn unbind(&self) {
-        context::with(|ctx| ctx.bind_texture(GL::TEXTURE_2D, None))
+        let _ = context::try_with_id(self.context, |ctx| ctx.bind_texture(GL::TEXTURE_2D, None));
+    }
+
+    /// 保持している情報からテクスチャを作り直す
+    fn restore(&self, ctx: &Context) {
+        *self.gl_texture.borrow_mut() = ctx.create_texture();
+        self.generation.set(ctx.generation());
+
+        let recipe = self.recipe.borrow();
+        let (width, height) = match recipe.size {
+            Some(size) => size,
+            None => return,
+        };
+
+        ctx.bind_texture(GL::TEXTURE_2D, self.gl_texture.borrow().as_ref());
+        // 失敗した場合は空のテクスチャのままにする
+        let _ = tex_image_2d(ctx, recipe.pixels.as_deref(), width, height);
+        if recipe.mipmap {
+            ctx.generate_mipmap(GL::TEXTURE_2D);
+        }
+        for (name, value) in recipe.parameters.iter() {
+            ctx.tex_parameteri(GL::TEXTURE_2D, *name, *value as i32);
+        }
+    }
+
+    /// GlFramebufferなどにアタッチするための、テクスチャオブジェクトそのもの
+    pub(crate) fn gl_texture(&self) -> Option<web_sys::WebGlTexture> {
+        self.gl_texture.borrow().clone()
+    }
+
+    /// RGBAの画素データを設定する
+    /// この関数を呼び出す前に、bindを実行しておく必要がある
+    ///
+    /// WebGL1では、縦横が2の冪乗でない画像にはミップマップとリピートが使えない。
+    /// その場合は縮小時の補完方法を `Linear` に、範囲外の値の扱いを `ClampToEdge` にする。
+    pub fn attach_img(&self, pixels: &[u8], width: i32, height: i32) -> Result<(), Error> {
+        let expected_len = width.max(0) as usize * height.max(0) as usize * 4;
+        if pixels.len() != expected_len {
+            return Err(Error::InvalidTextureSize { width, height });
+        }
+
+        self.set_image(Some(pixels.to_vec()), width, height)?;
+        if !self.supports_mipmap() {
+            self.set_minify_filter(MinMethod::Linear);
+            self.set_wrap_s(WrapMethod::ClampToEdge);
+            self.set_wrap_t(WrapMethod::ClampToEdge);
+        }
+        Ok(())
+    }
+
+    /// 設定されている画像で、ミップマップとリピートが使えるかどうか
+    /// WebGL1では、縦横が2の冪乗の画像でのみ使える
+    pub fn supports_mipmap(&self) -> bool {
+        let npot_textures = context::with_id(self.context, |ctx| ctx.capabilities().npot_textures);
+        match self.recipe.borrow().size {
+            Some((width, height)) => {
+                npot_textures || (is_power_of_two(width) && is_power_of_two(height))
+            }
+            None => false,
+        }
     }
 
-    /// 画像サイズは、縦横それぞれ2の冪乗でなければならない
+    /// 画素データを持たない領域を確保する
+    /// フレームバッファの描画先として使うことを想定しているため、
+    /// 画像サイズは2の冪乗である必要はない
     /// この関数を呼び出す前に、bindを実行しておく必要がある
-    pub fn attach_img(&self, pixels: &[u8], width: i32, height: i32) -> Result<(), JsValue> {
-        assert_eq!(width.count_ones(), 1);
-        assert_eq!(height.count_ones(), 1);
+    pub fn allocate(&self, width: i32, height: i32) -> Result<(), Error> {
+        self.set_image(None, width, height)
+    }
 
-        context::with(|ctx| {
-            ctx.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-                GL::TEXTURE_2D,  // target
-                0,               // level
-                GL::RGBA as i32, // internal format
-                width,
-                height,
-                0,                 // border. Must be 0.
-                GL::RGBA,          // format
-                GL::UNSIGNED_BYTE, // type
-                Some(pixels),
-            )
-        })
+    fn set_image(&self, pixels: Option<Vec<u8>>, width: i32, height: i32) -> Result<(), Error> {
+        let max_size =
+            context::try_with_id(self.context, |ctx| ctx.capabilities().max_texture_size)?;
+        let in_range = |n: i32| 0 < n && (max_size <= 0 || n <= max_size);
+        if !(in_range(width) && in_range(height)) {
+            return Err(Error::InvalidTextureSize { width, height });
+        }
+
+        context::try_with_id(self.context, |ctx| {
+            tex_image_2d(ctx, pixels.as_deref(), width, height)
+        })??;
+
+        let mut recipe = self.recipe.borrow_mut();
+        recipe.pixels = pixels;
+        recipe.size = Some((width, height));
+        // 画像を設定し直した場合、以前のミップマップは使われない
+        recipe.mipmap = false;
+        Ok(())
     }
 
+    /// ミップマップを生成する
+    /// `supports_mipmap` が `false` の場合は何もしない
     /// この関数を呼び出す前に、bindを実行しておく必要がある
     pub fn generate_mipmap(&self) {
-        context::with(|ctx| ctx.generate_mipmap(GL::TEXTURE_2D))
+        if !self.supports_mipmap() {
+            return;
+        }
+        context::with_id(self.context, |ctx| ctx.generate_mipmap(GL::TEXTURE_2D));
+        self.recipe.borrow_mut().mipmap = true;
     }
 
     /// 縮小表示するときの補完方法を指定する
     /// この関数を呼び出す前に、bindを実行しておく必要がある
     pub fn set_minify_filter(&self, method: MinMethod) {
-        context::with(|ctx| {
-            ctx.tex_parameteri(
-                GL::TEXTURE_2D,
-                GL::TEXTURE_MIN_FILTER,
-                method.to_gl() as i32,
-            )
-        })
+        self.set_parameter(GL::TEXTURE_MIN_FILTER, method.to_gl());
     }
 
     /// 拡大表示するときの保管方法を指定する
     /// この関数を呼び出す前に、bindを実行しておく必要がある
     pub fn set_magnify_filter(&self, method: MagMethod) {
-        context::with(|ctx| {
-            ctx.tex_parameteri(
-                GL::TEXTURE_2D,
-                GL::TEXTURE_MAG_FILTER,
-                method.to_gl() as i32,
-            )
-        })
+        self.set_parameter(GL::TEXTURE_MAG_FILTER, method.to_gl());
     }
 
     pub fn set_wrap_s(&self, method: WrapMethod) {
-        context::with(|ctx| {
-            ctx.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, method.to_gl() as i32)
-        })
+        self.set_parameter(GL::TEXTURE_WRAP_S, method.to_gl());
     }
 
     pub fn set_wrap_t(&self, method: WrapMethod) {
-        context::with(|ctx| {
-            ctx.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, method.to_gl() as i32)
-        })
+        self.set_parameter(GL::TEXTURE_WRAP_T, method.to_gl());
+    }
+
+    fn set_parameter(&self, name: u32, value: u32) {
+        context::with_id(self.context, |ctx| {
+            ctx.tex_parameteri(GL::TEXTURE_2D, name, value as i32)
+        });
+
+        let mut recipe = self.recipe.borrow_mut();
+        recipe.parameters.retain(|(n, _)| *n != name);
+        recipe.parameters.push((name, value));
+    }
+}
+
+impl Drop for GlTexture {
+    fn drop(&mut self) {
+        let texture = self.gl_texture.borrow_mut().take();
+        context::delete_resource(
+            self.context,
+            self.generation.get(),
+            ResourceKind::Texture,
+            move |ctx| ctx.delete_texture(texture.as_ref()),
+        );
     }
 }
 
+fn is_power_of_two(n: i32) -> bool {
+    n > 0 && (n as u32).is_power_of_two()
+}
+
+/// bindされているテクスチャに画像を設定する
+/// `pixels` が `None` の場合は領域の確保のみを行う
+fn tex_image_2d(
+    ctx: &Context,
+    pixels: Option<&[u8]>,
+    width: i32,
+    height: i32,
+) -> Result<(), Error> {
+    ctx.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+        GL::TEXTURE_2D,  // target
+        0,               // level
+        GL::RGBA as i32, // internal format
+        width,
+        height,
+        0,                 // border. Must be 0.
+        GL::RGBA,          // format
+        GL::UNSIGNED_BYTE, // type
+        pixels,
+    )
+    .map_err(Error::Js)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GlTextureUnit {
     Unit0,
     Unit1,
@@ -207,3 +361,59 @@ impl WrapMethod {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{capabilities::BackendPreference, test_util};
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_npot_image_on_webgl1() {
+        let id = context::create_with(&test_util::canvas(), BackendPreference::WebGl1).unwrap();
+        context::make_current(id);
+
+        let texture = GlTexture::new().unwrap();
+        texture.bind().unwrap();
+        texture.attach_img(&[255; 3 * 3 * 4], 3, 3).unwrap();
+        assert!(!texture.supports_mipmap());
+
+        texture.attach_img(&[255; 4 * 4 * 4], 4, 4).unwrap();
+        assert!(texture.supports_mipmap());
+
+        assert_eq!(
+            texture.attach_img(&[255; 4], 4, 4),
+            Err(Error::InvalidTextureSize {
+                width: 4,
+                height: 4
+            })
+        );
+
+        drop(texture);
+        context::destroy(id);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_recreate_after_restore() {
+        let id = context::create(&test_util::canvas()).unwrap();
+        context::make_current(id);
+
+        let texture = GlTexture::new().unwrap();
+        texture.bind().unwrap();
+        texture.attach_img(&[255; 4 * 4 * 4], 4, 4).unwrap();
+        texture.set_minify_filter(MinMethod::Nearest);
+        let old_texture = texture.gl_texture();
+
+        context::with(|ctx| ctx.on_restored());
+        texture.bind().unwrap();
+        assert_ne!(texture.gl_texture(), old_texture);
+
+        // 保持していたパラメータが設定し直されている
+        let filter =
+            context::with(|ctx| ctx.get_tex_parameter(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER));
+        assert_eq!(filter.as_f64(), Some(GL::NEAREST as f64));
+
+        drop(texture);
+        context::destroy(id);
+    }
+}
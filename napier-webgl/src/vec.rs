@@ -34,6 +34,27 @@ where
             self.vec.push(array[i]);
         }
     }
+
+    /// 格納されている `A` の個数を返す
+    pub fn count(&self) -> usize {
+        self.vec.len() / A::len()
+    }
+
+    /// `idx` 番目の `A` を返す
+    pub fn get(&self, idx: usize) -> Option<A> {
+        let start = idx * A::len();
+        let elements = self.vec.get(start..start + A::len())?;
+
+        let mut array = A::from_value(elements[0]);
+        for (i, e) in elements.iter().enumerate() {
+            array[i] = *e;
+        }
+        Some(array)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = A> + '_ {
+        (0..self.count()).filter_map(move |idx| self.get(idx))
+    }
 }
 
 impl<A> AsRef<[A::Element]> for StepVec<A>
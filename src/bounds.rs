@@ -0,0 +1,142 @@
+use crate::ray::Ray;
+use cgmath::{prelude::*, vec3, Vector3};
+
+/// 各軸に平行な辺を持つ直方体 (Axis Aligned Bounding Box)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// 全ての点を含む最小のAabbを返す
+    /// 点が一つもない場合は `None` を返す
+    pub fn from_points<I>(points: I) -> Option<Aabb>
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        let aabb = points.fold(Aabb::new(first, first), |aabb, p| Aabb {
+            min: vec3(
+                aabb.min.x.min(p.x),
+                aabb.min.y.min(p.y),
+                aabb.min.z.min(p.z),
+            ),
+            max: vec3(
+                aabb.max.x.max(p.x),
+                aabb.max.y.max(p.y),
+                aabb.max.z.max(p.z),
+            ),
+        });
+        Some(aabb)
+    }
+
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    /// 8つの頂点を返す
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            vec3(min.x, min.y, min.z),
+            vec3(max.x, min.y, min.z),
+            vec3(min.x, max.y, min.z),
+            vec3(max.x, max.y, min.z),
+            vec3(min.x, min.y, max.z),
+            vec3(max.x, min.y, max.z),
+            vec3(min.x, max.y, max.z),
+            vec3(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Rayと交差する場合、Rayの始点から交点までの距離を返す。
+    /// Rayの始点がAabbの内部にある場合は0を返す。
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        // スラブ法
+        let mut t_min = 0.0_f32;
+        let mut t_max = f32::INFINITY;
+
+        for axis in 0..3 {
+            let (origin, dir) = (ray.origin[axis], ray.dir[axis]);
+            let (min, max) = (self.min[axis], self.max[axis]);
+
+            if dir == 0.0 {
+                // 軸に平行なRayは、スラブの内側にあるかどうかだけを見る
+                if origin < min || max < origin {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (min - origin) / dir;
+            let t2 = (max - origin) / dir;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+/// 球
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Sphere {
+    /// 全ての点を含む球を返す
+    /// 中心は全ての点を含むAabbの中心とするため、最小の球になるとは限らない
+    /// 点が一つもない場合は `None` を返す
+    pub fn from_points<I>(points: I) -> Option<Sphere>
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+    {
+        let points = points.into_iter().collect::<Vec<_>>();
+        let center = Aabb::from_points(points.iter().copied())?.center();
+        let radius = points
+            .iter()
+            .fold(0.0_f32, |r, p| r.max(p.distance(center)));
+        Some(Sphere::new(center, radius))
+    }
+
+    pub fn new(center: Vector3<f32>, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_intersect_ray() {
+        let aabb = Aabb::from_points(vec![vec3(-1.0, -1.0, -1.0), vec3(1.0, 2.0, 1.0)]).unwrap();
+
+        let ray = Ray::new(vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_relative_eq!(aabb.intersect_ray(&ray).unwrap(), 4.0);
+
+        let inside = Ray::new(vec3(0.0, 1.5, 0.0), vec3(1.0, 0.0, 0.0));
+        assert_relative_eq!(aabb.intersect_ray(&inside).unwrap(), 0.0);
+
+        let miss = Ray::new(vec3(0.0, 3.0, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(aabb.intersect_ray(&miss), None);
+
+        let away = Ray::new(vec3(0.0, 0.0, 5.0), vec3(0.0, 0.0, 1.0));
+        assert_eq!(aabb.intersect_ray(&away), None);
+    }
+}
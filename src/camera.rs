@@ -1,4 +1,5 @@
-use cgmath::{prelude::*, Deg, Matrix4, Point3, Rad, Vector3};
+use crate::{frustum::Frustum, ray::Ray};
+use cgmath::{prelude::*, vec4, Deg, Matrix4, Point3, Rad, Vector3};
 
 /// カメラを表すモデル
 ///
@@ -18,12 +19,10 @@ pub struct Camera {
     /// Default : (0.0, 0.0, 1.0),
     pub up: Vector3<f32>,
 
-    /// 画角
-    /// Default : Deg(45.0)
-    pub fovy: Rad<f32>,
-    /// アスペクト比
-    /// Default : 1.0
-    pub aspect: f32,
+    /// 投影方法
+    /// Default : Projection::Perspective { fovy: Deg(45.0), aspect: 1.0 }
+    pub projection: Projection,
+
     /// どれくらい近くまで写すか
     /// Default : 0.1
     pub near: f32,
@@ -39,23 +38,246 @@ impl Camera {
             pos: Vector3::zero(),
             look_at: Vector3::zero(),
             up: Vector3::new(0.0, 0.0, 1.0),
-            fovy: Deg(45.0).into(),
-            aspect: 1.0,
+            projection: Projection::perspective(Deg(45.0), 1.0),
             near: 0.1,
             far: 100.0,
         }
     }
 
-    /// ( プロジェクション座標変換行列 ) * ( ビュー座標変換行列 ) の計算結果を返す
-    pub(crate) fn matrix(&self) -> Matrix4<f32> {
-        let p_mat = cgmath::perspective(self.fovy, self.aspect, self.near, self.far);
+    /// 投影方法を保ったままアスペクト比を変更する。
+    /// 平行投影の場合は、写す範囲の高さと中心を保ったまま幅を変更する。
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.projection = match self.projection {
+            Projection::Perspective { fovy, .. } => Projection::Perspective { fovy, aspect },
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            } => {
+                let center = (left + right) / 2.0;
+                let half_width = (top - bottom) * aspect / 2.0;
+                Projection::Orthographic {
+                    left: center - half_width,
+                    right: center + half_width,
+                    bottom,
+                    top,
+                }
+            }
+        };
+    }
 
-        let v_mat = Matrix4::look_at(
+    /// ビュー座標変換行列を返す
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at(
             Point3::from_vec(self.pos),
             Point3::from_vec(self.look_at),
             self.up,
-        );
+        )
+    }
+
+    /// プロジェクション座標変換行列を返す
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection.matrix(self.near, self.far)
+    }
+
+    /// ( プロジェクション座標変換行列 ) * ( ビュー座標変換行列 ) の計算結果を返す
+    pub(crate) fn matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// カメラに写るworld座標系の範囲を返す
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.matrix())
+    }
+
+    /// 画面上の点を通るworld座標系のRayを返す。
+    /// Rayの始点はnear面上の点となる。
+    /// `pos` と `look_at` が同じ位置にある場合など、カメラの向きが定まらない場合は `None` を返す。
+    ///
+    /// `x`, `y` は描画領域の左上を原点とした座標で、
+    /// `napier_window::event::MouseEvent::x()` などの値をそのまま渡せる。
+    pub fn screen_point_to_ray(
+        &self,
+        x: f64,
+        y: f64,
+        viewport_width: u32,
+        viewport_height: u32,
+    ) -> Option<Ray> {
+        // 正規化デバイス座標に変換する
+        let ndc_x = (x / viewport_width as f64 * 2.0 - 1.0) as f32;
+        let ndc_y = (1.0 - y / viewport_height as f64 * 2.0) as f32;
+
+        let inv_matrix = self.matrix().invert()?;
+        let near = inv_matrix * vec4(ndc_x, ndc_y, -1.0, 1.0);
+        let far = inv_matrix * vec4(ndc_x, ndc_y, 1.0, 1.0);
+        let near = near.truncate() / near.w;
+        let far = far.truncate() / far.w;
+        if !near.is_finite() || !far.is_finite() {
+            return None;
+        }
+
+        Some(Ray::new(near, far - near))
+    }
+
+    /// world座標系の点が画面上のどこに写るかを返す。
+    /// 返り値の座標は描画領域の左上を原点とする。
+    /// 点がカメラの後ろにある場合や、`near` から `far` までの範囲にない場合は `None` を返す。
+    /// 描画領域の外に写る点の場合は、描画領域の外の座標を返す。
+    pub fn world_to_screen(
+        &self,
+        point: Vector3<f32>,
+        viewport_width: u32,
+        viewport_height: u32,
+    ) -> Option<(f64, f64)> {
+        // View座標系ではカメラは -Z 方向を向いている
+        // 平行投影では w が常に1になるため、深度で判定する
+        let view = self.view_matrix() * point.extend(1.0);
+        if !(self.near..=self.far).contains(&-view.z) {
+            return None;
+        }
+
+        let clip = self.projection_matrix() * view;
+
+        let ndc_x = (clip.x / clip.w) as f64;
+        let ndc_y = (clip.y / clip.w) as f64;
+        let x = (ndc_x + 1.0) / 2.0 * viewport_width as f64;
+        let y = (1.0 - ndc_y) / 2.0 * viewport_height as f64;
+        Some((x, y))
+    }
+}
+
+/// カメラの投影方法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// 透視投影
+    /// 遠くのものほど小さく写る
+    Perspective {
+        /// 画角
+        fovy: Rad<f32>,
+        /// アスペクト比
+        aspect: f32,
+    },
+    /// 平行投影
+    /// 距離によらず同じ大きさで写る。盤面の真上からの視点やHUDなどに使う。
+    /// 各値はカメラから見た写す範囲を表す。
+    Orthographic {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+}
 
-        p_mat * v_mat
+impl Projection {
+    pub fn perspective<A>(fovy: A, aspect: f32) -> Projection
+    where
+        A: Into<Rad<f32>>,
+    {
+        Projection::Perspective {
+            fovy: fovy.into(),
+            aspect,
+        }
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32) -> Projection {
+        Projection::Orthographic {
+            left,
+            right,
+            bottom,
+            top,
+        }
+    }
+
+    /// 縦方向に `height` の範囲を写す平行投影を生成する
+    /// 横方向の範囲は `height * aspect` となる
+    pub fn orthographic_with_height(height: f32, aspect: f32) -> Projection {
+        let half_height = height / 2.0;
+        let half_width = half_height * aspect;
+        Projection::orthographic(-half_width, half_width, -half_height, half_height)
+    }
+
+    /// プロジェクション座標変換行列を返す
+    pub(crate) fn matrix(&self, near: f32, far: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective { fovy, aspect } => {
+                cgmath::perspective(fovy, aspect, near, far)
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            } => cgmath::ortho(left, right, bottom, top, near, far),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use cgmath::vec3;
+    use wasm_bindgen_test::*;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new();
+        camera.pos = vec3(0.0, 0.0, 20.0);
+        camera.up = vec3(0.0, 1.0, 0.0);
+        camera
+    }
+
+    #[wasm_bindgen_test]
+    fn test_screen_point_to_ray() {
+        let camera = camera();
+
+        let ray = camera.screen_point_to_ray(200.0, 150.0, 400, 300).unwrap();
+        assert_relative_eq!(ray.dir, vec3(0.0, 0.0, -1.0), epsilon = 1e-5);
+        assert_relative_eq!(ray.origin, vec3(0.0, 0.0, 19.9), epsilon = 1e-4);
+
+        let mut camera = camera;
+        camera.projection = Projection::orthographic_with_height(10.0, 1.0);
+        let ray = camera.screen_point_to_ray(0.0, 0.0, 400, 400).unwrap();
+        assert_relative_eq!(ray.dir, vec3(0.0, 0.0, -1.0), epsilon = 1e-5);
+        assert_relative_eq!(ray.origin, vec3(-5.0, 5.0, 19.9), epsilon = 1e-4);
+
+        // 向きが定まらないカメラ
+        let mut camera = camera;
+        camera.look_at = camera.pos;
+        assert!(camera.screen_point_to_ray(0.0, 0.0, 400, 400).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_world_to_screen() {
+        let camera = camera();
+
+        let point = vec3(3.0, -2.0, 1.0);
+        let (x, y) = camera.world_to_screen(point, 400, 300).unwrap();
+        let ray = camera.screen_point_to_ray(x, y, 400, 300).unwrap();
+        // 点はRay上にある
+        let t = (point - ray.origin).dot(ray.dir);
+        assert_relative_eq!(ray.at(t), point, epsilon = 1e-3);
+
+        assert_eq!(camera.world_to_screen(vec3(0.0, 0.0, 30.0), 400, 300), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_world_to_screen_orthographic() {
+        let mut camera = camera();
+        camera.projection = Projection::orthographic_with_height(10.0, 1.0);
+
+        let (x, y) = camera
+            .world_to_screen(vec3(-5.0, 5.0, 0.0), 400, 400)
+            .unwrap();
+        assert_relative_eq!(x, 0.0, epsilon = 1e-3);
+        assert_relative_eq!(y, 0.0, epsilon = 1e-3);
+
+        // カメラの後ろにある点
+        assert_eq!(camera.world_to_screen(vec3(0.0, 0.0, 30.0), 400, 400), None);
+        // farより奥にある点
+        assert_eq!(
+            camera.world_to_screen(vec3(0.0, 0.0, -200.0), 400, 400),
+            None
+        );
     }
 }
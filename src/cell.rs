@@ -1,6 +1,21 @@
 use cgmath::{vec3, Vector3};
 use std::{cell::Cell as StdCell, ops::AddAssign};
 
+thread_local! {
+    // 全てのCellで共有する世代番号。値が変更されるたびに増加する
+    static GENERATION: StdCell<u64> = const { StdCell::new(0) };
+}
+
+/// 新しい世代番号を発行する。
+/// 発行される番号は、それまでに発行されたどの番号よりも大きい。
+pub(crate) fn next_generation() -> u64 {
+    GENERATION.with(|g| {
+        let next = g.get() + 1;
+        g.set(next);
+        next
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct Vector3Cell<T>
 where
@@ -32,6 +47,14 @@ where
         self.y.set(y);
         self.z.set(z);
     }
+
+    /// いずれかの要素が最後に変更されたときの世代番号を返す
+    pub fn generation(&self) -> u64 {
+        self.x
+            .generation()
+            .max(self.y.generation())
+            .max(self.z.generation())
+    }
 }
 
 impl Vector3Cell<f32> {
@@ -40,23 +63,39 @@ impl Vector3Cell<f32> {
     }
 }
 
+/// 値が変更されたことを検知できる `std::cell::Cell`
+///
+/// 値を変更するたびに世代番号が更新されるため、
+/// 計算結果のキャッシュが古くなったかどうかを判定するのに使える。
 #[derive(Debug, Clone)]
-pub struct Cell<T: Copy>(StdCell<T>);
+pub struct Cell<T: Copy> {
+    value: StdCell<T>,
+    generation: StdCell<u64>,
+}
 
 impl<T> Cell<T>
 where
     T: Copy,
 {
     pub fn new(t: T) -> Self {
-        Cell(StdCell::new(t))
+        Cell {
+            value: StdCell::new(t),
+            generation: StdCell::new(next_generation()),
+        }
     }
 
     pub fn get(&self) -> T {
-        self.0.get()
+        self.value.get()
     }
 
     pub fn set(&self, t: T) {
-        self.0.set(t);
+        self.value.set(t);
+        self.generation.set(next_generation());
+    }
+
+    /// 最後に値が変更されたときの世代番号を返す
+    pub fn generation(&self) -> u64 {
+        self.generation.get()
     }
 
     /// This method is almost equivalent to `+=` operation except that this does not require
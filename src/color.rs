@@ -22,6 +22,10 @@ impl Color {
         Color::rgb(0, 0, 0)
     }
 
+    pub fn white() -> Self {
+        Color::rgb(255, 255, 255)
+    }
+
     /// 0 ~ 1 の範囲に丸めた値を返す
     pub fn to_f32(self) -> (f32, f32, f32, f32) {
         (
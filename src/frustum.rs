@@ -0,0 +1,127 @@
+use crate::object::Object;
+use cgmath::{prelude::*, Matrix4, Vector3, Vector4};
+
+/// 平面
+/// `normal・p + distance >= 0` となる点 `p` を内側とする
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    /// 内側を向いた単位法線ベクトル
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// `ax + by + cz + d = 0` を表すベクトル (a, b, c, d) から生成する
+    /// 法線ベクトルは正規化される
+    fn from_vec4(v: Vector4<f32>) -> Plane {
+        let normal = v.truncate();
+        let len = normal.magnitude();
+        Plane {
+            normal: normal / len,
+            distance: v.w / len,
+        }
+    }
+
+    /// 点から平面までの符号付き距離を返す
+    /// 内側にある場合は正になる
+    pub fn signed_distance(&self, p: Vector3<f32>) -> f32 {
+        self.normal.dot(p) + self.distance
+    }
+}
+
+/// カメラに写る範囲を表す視錐台
+///
+/// ```rust,ignore
+/// let frustum = camera.frustum();
+/// if frustum.intersects_object(&object) { ... }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// left, right, bottom, top, near, far の順に並んだ6つの平面
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// World座標をクリップ座標に変換する行列から視錐台を生成する
+    pub fn from_matrix(m: Matrix4<f32>) -> Frustum {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        Frustum {
+            planes: [
+                Plane::from_vec4(r3 + r0),
+                Plane::from_vec4(r3 - r0),
+                Plane::from_vec4(r3 + r1),
+                Plane::from_vec4(r3 - r1),
+                Plane::from_vec4(r3 + r2),
+                Plane::from_vec4(r3 - r2),
+            ],
+        }
+    }
+
+    /// 球と視錐台が重なるかどうか
+    pub fn intersects_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    /// 点の集まりが、いずれかの平面の完全に外側にあれば `false` を返す
+    /// 外側にあると判定できない場合は、実際には重なっていなくても `true` を返すことがある
+    pub fn intersects_points(&self, points: &[Vector3<f32>]) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| points.iter().any(|p| plane.signed_distance(*p) >= 0.0))
+    }
+
+    /// Objectが視錐台と重なる可能性があるかどうか
+    /// Meshの境界球で判定し、重なる場合はさらに境界ボックスで判定する
+    /// 頂点を持たないMeshは常に `true` を返す
+    pub fn intersects_object(&self, object: &Object) -> bool {
+        let mesh = &object.mesh;
+        let (sphere, bounds) = match (mesh.bounding_sphere, mesh.bounds) {
+            (Some(sphere), Some(bounds)) => (sphere, bounds),
+            _ => return true,
+        };
+
+        let m_matrix = object.transform.world_matrix();
+        let center = (m_matrix * sphere.center.extend(1.0)).truncate();
+        // 拡大率が軸ごとに異なる場合に備え、最も大きい拡大率を使う
+        let scale = (0..3)
+            .map(|i| m_matrix[i].truncate().magnitude())
+            .fold(0.0_f32, f32::max);
+        if !self.intersects_sphere(center, sphere.radius * scale) {
+            return false;
+        }
+
+        let corners = bounds
+            .corners()
+            .iter()
+            .map(|p| (m_matrix * p.extend(1.0)).truncate())
+            .collect::<Vec<_>>();
+        self.intersects_points(&corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Camera;
+    use cgmath::vec3;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_intersects_sphere() {
+        let mut camera = Camera::new();
+        camera.pos = vec3(0.0, 0.0, 10.0);
+        camera.up = vec3(0.0, 1.0, 0.0);
+        let frustum = camera.frustum();
+
+        assert!(frustum.intersects_sphere(vec3(0.0, 0.0, 0.0), 1.0));
+        // カメラの後ろ
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, 20.0), 1.0));
+        // 画面の外
+        assert!(!frustum.intersects_sphere(vec3(10.0, 0.0, 0.0), 1.0));
+        // 画面の外だが、半径が大きいため一部が写る
+        assert!(frustum.intersects_sphere(vec3(10.0, 0.0, 0.0), 7.0));
+        // farより遠い
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, -200.0), 1.0));
+    }
+}
@@ -1,8 +1,16 @@
+mod bounds;
 mod camera;
 mod color;
+mod frustum;
+mod material;
 mod object;
+mod picking;
+mod ray;
+mod render_queue;
 mod renderer;
 mod scene;
+mod shadow;
+mod stats;
 mod texture;
 
 pub mod cell;
@@ -13,21 +21,100 @@ pub mod programs;
 #[cfg(test)]
 mod original;
 
-pub use camera::Camera;
+// WebGLのContextを使うテストがあるため、ブラウザ上で実行する
+#[cfg(test)]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use wasm_bindgen::JsCast as _;
+
+    /// テスト用に、documentに追加していないcanvasを生成する
+    pub fn canvas() -> crate::window::Canvas {
+        let canvas = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into()
+            .unwrap();
+        crate::window::Canvas::from_element(canvas)
+    }
+}
+
+pub use bounds::{Aabb, Sphere};
+pub use camera::{Camera, Projection};
 pub use color::Color;
+pub use frustum::{Frustum, Plane};
 pub use light::Light;
+pub use material::{Material, ShadingModel};
 pub use meshes::Mesh;
 pub use object::{Object, Transform};
+pub use picking::Hit;
+pub use ray::Ray;
+pub use render_queue::SavedStateChanges;
 pub use renderer::Renderer;
-pub use scene::Scene;
+pub use scene::{ObjectId, Scene};
+pub use shadow::ShadowSettings;
+pub use stats::RenderStats;
 pub use texture::Texture;
 
+pub use napier_webgl::{
+    capabilities::{Backend, BackendPreference, Capabilities},
+    context::ContextId,
+    error::Error,
+};
+
 pub use napier_webgl as webgl;
 pub use napier_window as window;
 
-use wasm_bindgen::JsValue;
-
 /// `Canvas` 以外のオブジェクトを作成する前にこの `init` 関数を呼び出さなければいけない
-pub fn init(canvas: &window::Canvas) -> Result<(), JsValue> {
+/// WebGLのContextが失われた場合に復元されるよう、`canvas` のイベントの監視も開始する
+///
+/// 複数のcanvasに描画する場合は、代わりに `create_context` を使う。
+pub fn init(canvas: &window::Canvas) -> Result<(), Error> {
     webgl::context::initialize(canvas.as_ref())
 }
+
+/// `init` と同様だが、使用するWebGLのバージョンを選べる
+///
+/// ```rust,ignore
+/// napier::init_with(&canvas, BackendPreference::PreferWebGl2)?;
+/// let is_webgl2 = napier::webgl::context::with(|ctx| ctx.capabilities().backend == Backend::WebGl2);
+/// ```
+pub fn init_with(canvas: &window::Canvas, preference: BackendPreference) -> Result<(), Error> {
+    webgl::context::initialize_with(canvas.as_ref(), preference)
+}
+
+/// `canvas` に描画するためのWebGLのContextを新たに生成する
+///
+/// Mesh、Texture、Rendererなどは、生成した時点で操作対象のContextに属する。
+/// 別のcanvas用のオブジェクトを生成する前に `use_context` で操作対象を切り替える。
+///
+/// ```rust,ignore
+/// let board = napier::create_context(&board_canvas)?;
+/// let minimap = napier::create_context(&minimap_canvas)?;
+///
+/// napier::use_context(board);
+/// let mut board_renderer = Renderer::new()?;
+///
+/// napier::use_context(minimap);
+/// let mut minimap_renderer = Renderer::new()?;
+/// ```
+pub fn create_context(canvas: &window::Canvas) -> Result<ContextId, Error> {
+    webgl::context::create(canvas.as_ref())
+}
+
+/// 操作対象のContextを切り替える
+/// `Renderer::render` などは、そのRendererのContextに自動で切り替える
+pub fn use_context(id: ContextId) {
+    webgl::context::make_current(id)
+}
+
+/// 操作対象のWebGLのContextが失われているかどうか
+/// モバイル端末でタブを切り替えた場合などに失われ、ブラウザによって自動で復元される。
+/// 失われている間は `Renderer::render` は何も描画しない。
+pub fn is_context_lost() -> bool {
+    webgl::context::with(|ctx| ctx.is_lost())
+}
@@ -1,8 +1,15 @@
-use cgmath::{vec3, Vector3};
+use crate::Color;
+use cgmath::{vec2, vec3, Rad, Vector2, Vector3};
+
+/// 1つの `Scene` で同時に有効にできる光源の最大数
+///
+/// これを超えて追加された光源は描画時に無視される。
+pub const MAX_LIGHTS: usize = 4;
 
 pub enum Light {
     Directional(DirectionalLight),
     Point(PointLight),
+    Spot(SpotLight),
 }
 
 impl Light {
@@ -13,26 +20,203 @@ impl Light {
     pub fn point(x: f32, y: f32, z: f32) -> Light {
         Light::Point(PointLight::new(x, y, z))
     }
+
+    /// `pos` から `dir` の方向を照らすスポットライトを生成する
+    pub fn spot<A: Into<Rad<f32>>>(
+        pos: Vector3<f32>,
+        dir: Vector3<f32>,
+        inner_angle: A,
+        outer_angle: A,
+    ) -> Light {
+        Light::Spot(SpotLight::new(pos, dir, inner_angle, outer_angle))
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Light::Directional(light) => light.color,
+            Light::Point(light) => light.color,
+            Light::Spot(light) => light.color,
+        }
+    }
+
+    pub fn intensity(&self) -> f32 {
+        match self {
+            Light::Directional(light) => light.intensity,
+            Light::Point(light) => light.intensity,
+            Light::Spot(light) => light.intensity,
+        }
+    }
+
+    /// シェーダーに渡す、強さを掛け合わせた光の色
+    pub(crate) fn radiance(&self) -> Vector3<f32> {
+        let (r, g, b, _) = self.color().to_f32();
+        vec3(r, g, b) * self.intensity()
+    }
 }
 
 /// 平行光源
 pub struct DirectionalLight {
     pub dir: Vector3<f32>,
+    pub color: Color,
+    pub intensity: f32,
 }
 
 impl DirectionalLight {
+    /// 強さ1.0の白色光を生成する
     pub fn new(x: f32, y: f32, z: f32) -> Self {
-        DirectionalLight { dir: vec3(x, y, z) }
+        DirectionalLight {
+            dir: vec3(x, y, z),
+            color: Color::white(),
+            intensity: 1.0,
+        }
     }
 }
 
 /// 点光源
 pub struct PointLight {
     pub pos: Vector3<f32>,
+    pub color: Color,
+    pub intensity: f32,
+    pub attenuation: Attenuation,
 }
 
 impl PointLight {
+    /// 強さ1.0の、距離によって減衰しない白色光を生成する
     pub fn new(x: f32, y: f32, z: f32) -> Self {
-        PointLight { pos: vec3(x, y, z) }
+        PointLight {
+            pos: vec3(x, y, z),
+            color: Color::white(),
+            intensity: 1.0,
+            attenuation: Attenuation::none(),
+        }
+    }
+}
+
+/// スポットライト
+///
+/// 光軸からの角度が `inner_angle` 以内の範囲は最大の強さで照らし、
+/// `inner_angle` から `outer_angle` にかけて滑らかに暗くなる。
+pub struct SpotLight {
+    pub pos: Vector3<f32>,
+    pub dir: Vector3<f32>,
+    /// 光軸から、光が減衰し始めるまでの角度
+    pub inner_angle: Rad<f32>,
+    /// 光軸から、光が届かなくなるまでの角度
+    pub outer_angle: Rad<f32>,
+    pub color: Color,
+    pub intensity: f32,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    /// 強さ1.0の、距離によって減衰しない白色のスポットライトを生成する
+    /// `inner_angle` が `outer_angle` より大きい場合は入れ替える
+    pub fn new<A: Into<Rad<f32>>>(
+        pos: Vector3<f32>,
+        dir: Vector3<f32>,
+        inner_angle: A,
+        outer_angle: A,
+    ) -> Self {
+        let mut inner_angle = inner_angle.into();
+        let mut outer_angle = outer_angle.into();
+        if inner_angle > outer_angle {
+            std::mem::swap(&mut inner_angle, &mut outer_angle);
+        }
+
+        SpotLight {
+            pos,
+            dir,
+            inner_angle,
+            outer_angle,
+            color: Color::white(),
+            intensity: 1.0,
+            attenuation: Attenuation::none(),
+        }
+    }
+
+    /// シェーダーに渡す、(inner_angle, outer_angle) それぞれのcos
+    /// `inner_angle` が `outer_angle` より大きい場合は `outer_angle` に揃える
+    pub(crate) fn cone_cos(&self) -> Vector2<f32> {
+        let inner_angle = self.inner_angle.0.min(self.outer_angle.0);
+        vec2(inner_angle.cos(), self.outer_angle.0.cos())
+    }
+}
+
+/// 光源からの距離による光の減衰
+///
+/// 距離 `d` における光の強さは `1 / (constant + linear * d + quadratic * d^2)` 倍になる。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// `with_range` に指定できる最小の距離
+    pub const MIN_RANGE: f32 = 0.01;
+
+    pub fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    /// 距離によって減衰しない
+    pub fn none() -> Self {
+        Attenuation::new(1.0, 0.0, 0.0)
+    }
+
+    /// 光がおおよそ `range` の距離まで届くような減衰を生成する
+    /// `range` の位置では元の強さの1%程度になる。
+    ///
+    /// `range` が `MIN_RANGE` より小さい場合（0以下やNaNを含む）は `MIN_RANGE` として扱う。
+    /// `range` が無限大の場合は、距離によって減衰しない。
+    pub fn with_range(range: f32) -> Self {
+        // NaNの場合も `MIN_RANGE` になる
+        let range = range.max(Self::MIN_RANGE);
+        Attenuation::new(1.0, 4.5 / range, 75.0 / (range * range))
+    }
+
+    pub(crate) fn to_vec3(self) -> Vector3<f32> {
+        vec3(self.constant, self.linear, self.quadratic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_attenuation_with_range() {
+        let att = Attenuation::with_range(10.0);
+        let factor = |d: f32| 1.0 / (att.constant + att.linear * d + att.quadratic * d * d);
+
+        assert_relative_eq!(factor(0.0), 1.0);
+        assert!(factor(10.0) < 0.02);
+
+        let min = Attenuation::with_range(Attenuation::MIN_RANGE);
+        assert_eq!(Attenuation::with_range(0.0), min);
+        assert_eq!(Attenuation::with_range(-1.0), min);
+        assert_eq!(Attenuation::with_range(f32::NAN), min);
+        assert_eq!(Attenuation::with_range(f32::INFINITY), Attenuation::none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_spot_light_angles() {
+        let pos = vec3(0.0, 0.0, 5.0);
+        let dir = vec3(0.0, 0.0, -1.0);
+        let light = SpotLight::new(pos, dir, Rad(0.5), Rad(0.3));
+        assert_eq!(light.inner_angle, Rad(0.3));
+        assert_eq!(light.outer_angle, Rad(0.5));
+
+        let mut light = light;
+        light.inner_angle = Rad(0.8);
+        let cone_cos = light.cone_cos();
+        assert_relative_eq!(cone_cos.x, cone_cos.y);
     }
 }
@@ -0,0 +1,98 @@
+use crate::{cell::Cell, texture::Texture, Color};
+use cgmath::{vec3, Vector3, Vector4};
+use std::{cell::RefCell, rc::Rc};
+
+/// Objectの表面の質感を表すモデル
+///
+/// ```rust
+/// use napier::{Color, Material};
+///
+/// let material = Material::new();
+/// material.base_color.set(Color::rgb(255, 0, 0));
+/// material.shininess.set(10.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Material {
+    /// 頂点の色に掛け合わせる色
+    /// Default : 白
+    pub base_color: Cell<Color>,
+
+    /// 鏡面反射のハイライトの鋭さ。大きいほど小さく鋭いハイライトになる
+    /// Default : 50.0
+    pub shininess: Cell<f32>,
+
+    /// 鏡面反射の強さ
+    /// Default : 1.0
+    pub specular_strength: Cell<f32>,
+
+    /// 光源に関係なく、自ら発する色
+    /// Default : 黒
+    pub emissive: Cell<Color>,
+
+    /// 不透明度。`base_color` のアルファ値に掛け合わせる
+    /// Default : 1.0
+    pub opacity: Cell<f32>,
+
+    /// 陰影の付け方
+    /// Default : ShadingModel::Phong
+    pub shading: Cell<ShadingModel>,
+
+    texture: RefCell<Option<Rc<Texture>>>,
+}
+
+impl Material {
+    pub fn new() -> Self {
+        Material {
+            base_color: Cell::new(Color::white()),
+            shininess: Cell::new(50.0),
+            specular_strength: Cell::new(1.0),
+            emissive: Cell::new(Color::black()),
+            opacity: Cell::new(1.0),
+            shading: Cell::new(ShadingModel::Phong),
+            texture: RefCell::new(None),
+        }
+    }
+
+    /// 貼り付けるテクスチャ
+    /// 設定されていない場合は `Mesh` のテクスチャが使われる
+    pub fn texture(&self) -> Option<Rc<Texture>> {
+        self.texture.borrow().clone()
+    }
+
+    /// 貼り付けるテクスチャを設定する。
+    /// テクスチャ座標は `Mesh` のものが使われるため、
+    /// テクスチャ座標を持たない `Mesh` では無視される。
+    pub fn set_texture(&self, texture: Option<Rc<Texture>>) {
+        *self.texture.borrow_mut() = texture;
+    }
+
+    /// シェーダーに渡す、不透明度を掛け合わせた `base_color`
+    pub(crate) fn color_vec4(&self) -> Vector4<f32> {
+        let mut color = self.base_color.get().to_f32_vec4();
+        color.w *= self.opacity.get();
+        color
+    }
+
+    /// シェーダーに渡す `emissive` のRGB
+    pub(crate) fn emissive_vec3(&self) -> Vector3<f32> {
+        let (r, g, b, _) = self.emissive.get().to_f32();
+        vec3(r, g, b)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new()
+    }
+}
+
+/// 陰影の付け方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShadingModel {
+    /// ピクセルごとに光の計算を行う。高品質だが高負荷
+    Phong,
+    /// 頂点ごとに光の計算を行い、その間を補完する。低品質だが低負荷
+    Gouraud,
+    /// 光の影響を受けず、色をそのまま描画する
+    Unlit,
+}
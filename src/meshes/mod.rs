@@ -6,10 +6,14 @@ pub use rect::{rect, rect_with_texture};
 pub use sphere::sphere;
 pub use torus::torus;
 
-use crate::texture::Texture;
+use crate::{
+    bounds::{Aabb, Sphere},
+    texture::Texture,
+};
 use cgmath::{Vector2, Vector3, Vector4};
 use napier_webgl::{
     buffers::{IBO, VBO},
+    error::Error,
     vec::StepVec,
 };
 use std::rc::Rc;
@@ -22,6 +26,19 @@ pub struct Mesh {
     pub indexes_ibo: Rc<IBO<StepVec<Vector3<i16>>>>,
     pub index_len: i32,
     pub texture: Option<MeshTexture>,
+    /// CPU側に保持している頂点座標
+    /// ピッキングなど、GPUに転送したデータをCPU側で使う場合に参照する
+    pub positions: Rc<StepVec<Vector3<f32>>>,
+    /// CPU側に保持している頂点インデックス
+    pub indexes: Rc<StepVec<Vector3<i16>>>,
+    /// Local座標系で全ての頂点を含む直方体
+    /// 頂点が一つもない場合は `None`
+    pub bounds: Option<Aabb>,
+    /// Local座標系で全ての頂点を含む球
+    /// 頂点が一つもない場合は `None`
+    pub bounding_sphere: Option<Sphere>,
+    /// 頂点の色に半透明なものが含まれるかどうか
+    pub has_transparent_vertex: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,21 +53,39 @@ impl Mesh {
         colors: StepVec<Vector4<f32>>,
         normals: StepVec<Vector3<f32>>,
         indexes: StepVec<Vector3<i16>>,
-    ) -> Mesh {
-        Mesh {
-            positions_vbo: Rc::new(VBO::with_data(&positions)),
-            colors_vbo: Rc::new(VBO::with_data(&colors)),
-            normals_vbo: Rc::new(VBO::with_data(&normals)),
-            indexes_ibo: Rc::new(IBO::with_data(&indexes)),
+    ) -> Result<Mesh, Error> {
+        Ok(Mesh {
+            positions_vbo: Rc::new(VBO::with_data(&positions)?),
+            colors_vbo: Rc::new(VBO::with_data(&colors)?),
+            normals_vbo: Rc::new(VBO::with_data(&normals)?),
+            indexes_ibo: Rc::new(IBO::with_data(&indexes)?),
             index_len: indexes.as_ref().len() as i32,
             texture: None,
-        }
+            bounds: Aabb::from_points(positions.iter()),
+            bounding_sphere: Sphere::from_points(positions.iter()),
+            has_transparent_vertex: colors.iter().any(|color| color.w < 1.0),
+            positions: Rc::new(positions),
+            indexes: Rc::new(indexes),
+        })
+    }
+
+    /// `idx` 番目の三角形の各頂点のLocal座標を返す
+    pub fn triangle(&self, idx: usize) -> Option<[Vector3<f32>; 3]> {
+        let index = self.indexes.get(idx)?;
+        // インデックスは符号なしの値として扱う
+        let vertex = |i: i16| self.positions.get(i as u16 as usize);
+        Some([vertex(index.x)?, vertex(index.y)?, vertex(index.z)?])
     }
 
-    pub fn paste_texture(&mut self, coord: StepVec<Vector2<f32>>, data: Texture) {
+    pub fn paste_texture(
+        &mut self,
+        coord: StepVec<Vector2<f32>>,
+        data: Texture,
+    ) -> Result<(), Error> {
         self.texture = Some(MeshTexture {
-            coord: Rc::new(VBO::with_data(&coord)),
+            coord: Rc::new(VBO::with_data(&coord)?),
             data: Rc::new(data),
         });
+        Ok(())
     }
 }
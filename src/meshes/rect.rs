@@ -1,9 +1,9 @@
 use super::Mesh;
 use crate::{Color, Texture};
 use cgmath::{vec2, vec3, vec4, Vector2, Vector3, Vector4};
-use napier_webgl::vec::StepVec;
+use napier_webgl::{error::Error, vec::StepVec};
 
-pub fn rect(width: f32, height: f32, color: Color) -> Mesh {
+pub fn rect(width: f32, height: f32, color: Color) -> Result<Mesh, Error> {
     // Zを書くように頂点を設定する
     let half_width = width / 2.0;
     let half_height = height / 2.0;
@@ -35,8 +35,13 @@ pub fn rect(width: f32, height: f32, color: Color) -> Mesh {
     Mesh::new(positions, colors, normals, indexes)
 }
 
-pub fn rect_with_texture(width: f32, height: f32, color: Color, texture: Texture) -> Mesh {
-    let mut mesh = rect(width, height, color);
+pub fn rect_with_texture(
+    width: f32,
+    height: f32,
+    color: Color,
+    texture: Texture,
+) -> Result<Mesh, Error> {
+    let mut mesh = rect(width, height, color)?;
 
     // テクスチャ座標
     let mut tex_coord = StepVec::<Vector2<f32>>::new();
@@ -45,7 +50,7 @@ pub fn rect_with_texture(width: f32, height: f32, color: Color, texture: Texture
     tex_coord.push(vec2(0.0, 1.0)); // 左下
     tex_coord.push(vec2(1.0, 1.0)); // 右下
 
-    mesh.paste_texture(tex_coord, texture);
+    mesh.paste_texture(tex_coord, texture)?;
 
-    mesh
+    Ok(mesh)
 }
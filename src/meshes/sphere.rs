@@ -1,9 +1,9 @@
 use super::Mesh;
 use cgmath::{vec3, vec4, Vector3, Vector4};
-use napier_webgl::vec::StepVec;
+use napier_webgl::{error::Error, vec::StepVec};
 use palette::{Hsva, Srgba};
 
-pub fn sphere(row: usize, column: usize, radius: f32) -> Mesh {
+pub fn sphere(row: usize, column: usize, radius: f32) -> Result<Mesh, Error> {
     let mut pos = StepVec::<Vector3<f32>>::new();
     let mut nor = StepVec::<Vector3<f32>>::new();
     let mut col = StepVec::<Vector4<f32>>::new();
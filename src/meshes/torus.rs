@@ -1,11 +1,16 @@
 use super::Mesh;
 use cgmath::{prelude::*, vec3, vec4, Rad, Vector3, Vector4};
-use napier_webgl::vec::StepVec;
+use napier_webgl::{error::Error, vec::StepVec};
 use palette::{Hsva, Srgba};
 
 const CIRCLE_RAD: Rad<f32> = Rad(std::f32::consts::PI * 2.0);
 
-pub fn torus(tube_radius: f32, tube_steps: u32, core_radius: f32, core_steps: u32) -> Mesh {
+pub fn torus(
+    tube_radius: f32,
+    tube_steps: u32,
+    core_radius: f32,
+    core_steps: u32,
+) -> Result<Mesh, Error> {
     let mut positions = StepVec::<Vector3<f32>>::new();
     let mut colors = StepVec::<Vector4<f32>>::new();
     let mut indexes = StepVec::<Vector3<i16>>::new();
@@ -1,9 +1,13 @@
 use crate::{
-    cell::{Cell, Vector3Cell},
+    cell::{self, Cell, Vector3Cell},
+    material::Material,
     meshes::Mesh,
 };
-use cgmath::{prelude::*, Matrix4, Rad};
-use std::rc::Rc;
+use cgmath::{prelude::*, vec3, Euler, Matrix3, Matrix4, Quaternion, Rad, Vector3};
+use std::{
+    cell::{Cell as StdCell, RefCell},
+    rc::Rc,
+};
 
 /// world 上のオブジェクト
 /// `Mesh` を拡大縮小、回転、移動させたもの
@@ -11,6 +15,13 @@ use std::rc::Rc;
 pub struct Object {
     pub mesh: Mesh,
     pub transform: Rc<Transform>,
+    pub material: Rc<Material>,
+    /// 他のObjectに影を落とすかどうか
+    /// Default : true
+    pub casts_shadow: Rc<Cell<bool>>,
+    /// 他のObjectの影を受けるかどうか
+    /// Default : true
+    pub receives_shadow: Rc<Cell<bool>>,
 }
 
 impl Object {
@@ -19,6 +30,9 @@ impl Object {
         Object {
             mesh,
             transform: Rc::new(Transform::new()),
+            material: Rc::new(Material::new()),
+            casts_shadow: Rc::new(Cell::new(true)),
+            receives_shadow: Rc::new(Cell::new(true)),
         }
     }
 
@@ -30,8 +44,30 @@ impl Object {
         Object {
             mesh: self.mesh.clone(),
             transform: self.transform.clone(),
+            material: self.material.clone(),
+            casts_shadow: self.casts_shadow.clone(),
+            receives_shadow: self.receives_shadow.clone(),
         }
     }
+
+    /// `other` が `self` と同じObjectを指しているかどうかを返す。
+    /// `shared_clone` で生成されたObjectは生成元と同じObjectとみなされる。
+    pub fn is_same(&self, other: &Object) -> bool {
+        Rc::ptr_eq(&self.transform, &other.transform)
+    }
+
+    /// 親となるObjectを設定する。
+    /// 詳細は `Transform::set_parent` を参照。
+    pub fn set_parent(&self, parent: Option<&Object>) -> bool {
+        self.transform.set_parent(parent.map(|p| &p.transform))
+    }
+
+    /// world座標系での位置を保ったまま親となるObjectを設定する。
+    /// 詳細は `Transform::set_parent_keep_world` を参照。
+    pub fn set_parent_keep_world(&self, parent: Option<&Object>) -> bool {
+        self.transform
+            .set_parent_keep_world(parent.map(|p| &p.transform))
+    }
 }
 
 /// 新しいObjectを生成する。
@@ -42,6 +78,9 @@ impl Clone for Object {
         Object {
             mesh: self.mesh.clone(),
             transform: Rc::new(Transform::clone(&self.transform)),
+            material: Rc::new(Material::clone(&self.material)),
+            casts_shadow: Rc::new(Cell::new(self.casts_shadow.get())),
+            receives_shadow: Rc::new(Cell::new(self.receives_shadow.get())),
         }
     }
 }
@@ -53,6 +92,16 @@ impl Clone for Object {
 /// transform.rotate.axis.set(0.0, 1.0, 1.0);
 /// transform.rotate.angle.add(Rad(1.0));
 /// transform.pos.x.add(1);
+///
+/// ## 親子関係
+/// `set_parent` で親となる `Transform` を設定すると、
+/// `pos`, `rotate`, `scale` は親の座標系における値として扱われる。
+/// そのため親を動かすと子も一緒に動く。
+/// Cloneした場合、親子関係も引き継がれる。
+///
+/// ## キャッシュ
+/// 計算したモデル座標変換行列とその逆行列はキャッシュされ、
+/// `pos`, `rotate`, `scale` や親子関係が変更されるまで再利用される。
 #[derive(Debug, Clone)]
 pub struct Transform {
     // 移動方向
@@ -61,6 +110,12 @@ pub struct Transform {
     pub rotate: TransformRotate,
     // x, y, z 方向への拡大率
     pub scale: Vector3Cell<f32>,
+    // 親のTransform。Noneの場合はworld座標系に直接配置される
+    parent: RefCell<Option<Rc<Transform>>>,
+    // 親子関係が最後に変更されたときの世代番号
+    parent_generation: StdCell<u64>,
+    matrix_cache: MatrixCache,
+    world_matrix_cache: MatrixCache,
 }
 
 impl Transform {
@@ -69,14 +124,173 @@ impl Transform {
             pos: Vector3Cell::zero(),
             rotate: TransformRotate::new(),
             scale: Vector3Cell::new(1.0, 1.0, 1.0),
+            parent: RefCell::new(None),
+            parent_generation: StdCell::new(cell::next_generation()),
+            matrix_cache: MatrixCache::new(),
+            world_matrix_cache: MatrixCache::new(),
+        }
+    }
+
+    pub fn parent(&self) -> Option<Rc<Transform>> {
+        self.parent.borrow().clone()
+    }
+
+    /// 親となるTransformを設定する。`None` を渡すと親子関係を解除する。
+    /// `pos`, `rotate`, `scale` の値はそのままなので、
+    /// world座標系での位置は新しい親に応じて変化する。
+    ///
+    /// 親子関係が循環する場合は何も変更せずに `false` を返す。
+    pub fn set_parent(&self, parent: Option<&Rc<Transform>>) -> bool {
+        if !self.can_be_child_of(parent) {
+            return false;
         }
+        self.parent.replace(parent.cloned());
+        self.parent_generation.set(cell::next_generation());
+        true
+    }
+
+    /// world座標系での位置、回転、拡大率を保ったまま親となるTransformを設定する。
+    ///
+    /// 親の拡大率が不均一で、かつ子が親に対して回転している場合など、
+    /// せん断を含む変換は `pos`, `rotate`, `scale` で表現できないため近似される。
+    /// 親の拡大率が0の軸がある場合は位置を保てないため、`set_parent` と同じく
+    /// `pos`, `rotate`, `scale` を変更せずに親を設定する。
+    ///
+    /// 親子関係が循環する場合は何も変更せずに `false` を返す。
+    pub fn set_parent_keep_world(&self, parent: Option<&Rc<Transform>>) -> bool {
+        let world = self.world_matrix();
+        if !self.set_parent(parent) {
+            return false;
+        }
+
+        let local = match parent {
+            Some(parent) => match parent.inv_world_matrix() {
+                Some(inv_parent) => inv_parent * world,
+                None => return true,
+            },
+            None => world,
+        };
+        self.set_matrix(local);
+        true
+    }
+
+    /// `parent` を親にしても、親子関係が循環しないかどうか
+    fn can_be_child_of(&self, parent: Option<&Rc<Transform>>) -> bool {
+        parent.is_none_or(|parent| !parent.is_descendant_of(self))
+    }
+
+    /// `self` が `ancestor` 自身か、その子孫であるかどうか
+    fn is_descendant_of(&self, ancestor: &Transform) -> bool {
+        if std::ptr::eq(self, ancestor) {
+            return true;
+        }
+        match *self.parent.borrow() {
+            Some(ref parent) => parent.is_descendant_of(ancestor),
+            None => false,
+        }
+    }
+
+    /// `pos`, `rotate`, `scale` のいずれかが最後に変更されたときの世代番号
+    fn generation(&self) -> u64 {
+        self.pos
+            .generation()
+            .max(self.rotate.generation())
+            .max(self.scale.generation())
+    }
+
+    /// 自身または祖先のTransformが最後に変更されたときの世代番号
+    fn world_generation(&self) -> u64 {
+        let generation = self.generation().max(self.parent_generation.get());
+        match *self.parent.borrow() {
+            Some(ref parent) => generation.max(parent.world_generation()),
+            None => generation,
+        }
+    }
+
+    /// world座標変換行列を計算する
+    /// 親がいる場合は、( 親のworld座標変換行列 ) * ( モデル座標変換行列 ) となる
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world_matrix_cache
+            .matrix(self.world_generation(), || self.compute_world_matrix())
+    }
+
+    /// world座標変換行列の逆行列を計算する
+    /// 拡大率が0の場合など、逆行列が存在しない場合は `None` を返す
+    pub fn inv_world_matrix(&self) -> Option<Matrix4<f32>> {
+        self.world_matrix_cache
+            .inv_matrix(self.world_generation(), || self.compute_world_matrix())
+    }
+
+    /// 法線ベクトルをLocal座標系からworld座標系に変換する行列を返す。
+    /// `world_matrix` の左上3x3成分の逆転置行列の定数倍であり、
+    /// 拡大率が軸ごとに異なる場合でも法線が面と垂直に保たれる。
+    /// 変換後の法線は正規化されていないため、シェーダー側で正規化する必要がある。
+    ///
+    /// 逆行列を使わずに余因子行列から求めるため、拡大率が0の軸があっても計算できる。
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        let m = self.world_matrix();
+        let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+        // 余因子行列は ( 行列式 ) * ( 逆転置行列 ) に等しい
+        // 鏡映を含む場合に法線が反転しないよう、行列式の符号で補正する
+        let cofactor = Matrix3::from_cols(y.cross(z), z.cross(x), x.cross(y));
+        if x.dot(y.cross(z)) < 0.0 {
+            -cofactor
+        } else {
+            cofactor
+        }
+    }
+
+    fn compute_world_matrix(&self) -> Matrix4<f32> {
+        match *self.parent.borrow() {
+            Some(ref parent) => parent.world_matrix() * self.matrix(),
+            None => self.matrix(),
+        }
+    }
+
+    /// `target` の方向に +Z 軸が向くように回転させる。
+    /// `target` と `up` は `pos` と同じく親の座標系で指定する。
+    ///
+    /// `target` が `pos` と同じ位置にある場合は何もしない。
+    /// `up` が `target` の方向と平行な場合は、代わりにX軸かY軸を `up` として使う。
+    pub fn look_at(&self, target: Vector3<f32>, up: Vector3<f32>) {
+        let forward = target - self.pos.get();
+        if forward.magnitude2() == 0.0 {
+            return;
+        }
+        let forward = forward.normalize();
+
+        let mut right = up.cross(forward);
+        if right.magnitude2() == 0.0 {
+            // `forward` と平行でない方の軸を使う
+            let up = if forward.y.abs() < 0.9 {
+                vec3(0.0, 1.0, 0.0)
+            } else {
+                vec3(1.0, 0.0, 0.0)
+            };
+            right = up.cross(forward);
+        }
+        let right = right.normalize();
+        let up = forward.cross(right);
+        self.rotate
+            .set(Quaternion::from(Matrix3::from_cols(right, up, forward)));
     }
 
     // モデル座標変換行列を計算する
     pub fn matrix(&self) -> Matrix4<f32> {
+        self.matrix_cache
+            .matrix(self.generation(), || self.compute_matrix())
+    }
+
+    /// モデル座標変換行列の逆行列を計算する
+    /// 拡大率が0の場合など、逆行列が存在しない場合は `None` を返す
+    pub fn inv_matrix(&self) -> Option<Matrix4<f32>> {
+        self.matrix_cache
+            .inv_matrix(self.generation(), || self.compute_matrix())
+    }
+
+    fn compute_matrix(&self) -> Matrix4<f32> {
         let move_matrix = Matrix4::from_translation(self.pos.get());
-        let rotate_axis = self.rotate.axis.get().normalize();
-        let rotate_matrix = Matrix4::from_axis_angle(rotate_axis, self.rotate.angle.get());
+        let rotate_matrix = Matrix4::from(self.rotate.get());
         let scale = self.scale.get();
         let scale_matrix = Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
 
@@ -85,19 +299,471 @@ impl Transform {
         // move, rotate, scale の順でかけていく。
         move_matrix * rotate_matrix * scale_matrix
     }
+
+    /// モデル座標変換行列を `pos`, `rotate`, `scale` に分解して設定する
+    ///
+    /// 鏡映を含む場合は、X方向の拡大率を負の値にして表現する。
+    /// 拡大率が0の軸があり回転を求められない場合は、`rotate` を変更しない。
+    fn set_matrix(&self, m: Matrix4<f32>) {
+        self.pos.set(m.w.x, m.w.y, m.w.z);
+
+        let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+        let mut scale = vec3(x.magnitude(), y.magnitude(), z.magnitude());
+        if x.dot(y.cross(z)) < 0.0 {
+            scale.x = -scale.x;
+        }
+        self.scale.set(scale.x, scale.y, scale.z);
+
+        if scale.x != 0.0 && scale.y != 0.0 && scale.z != 0.0 {
+            let rotate_matrix = Matrix3::from_cols(x / scale.x, y / scale.y, z / scale.z);
+            self.rotate.set(Quaternion::from(rotate_matrix));
+        }
+    }
 }
 
+/// 世代番号と紐づけて、計算済みの行列とその逆行列を保持する
 #[derive(Debug, Clone)]
+struct MatrixCache(StdCell<Option<CachedMatrix>>);
+
+#[derive(Debug, Clone, Copy)]
+struct CachedMatrix {
+    generation: u64,
+    matrix: Matrix4<f32>,
+    // 逆行列は必要になったときに初めて計算する
+    // まだ計算していない場合は `None`、逆行列が存在しない場合は `Some(None)`
+    inv_matrix: Option<Option<Matrix4<f32>>>,
+}
+
+impl MatrixCache {
+    fn new() -> Self {
+        MatrixCache(StdCell::new(None))
+    }
+
+    /// キャッシュが `generation` のものであればそれを返し、
+    /// そうでなければ `compute` で計算し直す
+    fn cached<F>(&self, generation: u64, compute: F) -> CachedMatrix
+    where
+        F: FnOnce() -> Matrix4<f32>,
+    {
+        match self.0.get() {
+            Some(cached) if cached.generation == generation => cached,
+            _ => {
+                let cached = CachedMatrix {
+                    generation,
+                    matrix: compute(),
+                    inv_matrix: None,
+                };
+                self.0.set(Some(cached));
+                cached
+            }
+        }
+    }
+
+    fn matrix<F>(&self, generation: u64, compute: F) -> Matrix4<f32>
+    where
+        F: FnOnce() -> Matrix4<f32>,
+    {
+        self.cached(generation, compute).matrix
+    }
+
+    fn inv_matrix<F>(&self, generation: u64, compute: F) -> Option<Matrix4<f32>>
+    where
+        F: FnOnce() -> Matrix4<f32>,
+    {
+        let mut cached = self.cached(generation, compute);
+        if let Some(inv_matrix) = cached.inv_matrix {
+            return inv_matrix;
+        }
+
+        let inv_matrix = cached.matrix.invert();
+        cached.inv_matrix = Some(inv_matrix);
+        self.0.set(Some(cached));
+        inv_matrix
+    }
+}
+
+/// 回転を表現するモデル
+///
+/// 内部的にはクオータニオンとして保持しているため、
+/// 複数の回転を合成することができる。
+/// `axis`, `angle` を通して、回転軸と回転角による操作も可能。
+///
+/// ## Example
+/// // 回転軸と回転角で指定する
+/// rotate.axis.set(0.0, 1.0, 1.0);
+/// rotate.angle.add(Rad(1.0));
+///
+/// // カードを裏返してから、テーブル上で回転させる
+/// rotate.rotate_by(Quaternion::from_angle_x(Deg(180.0)));
+/// rotate.rotate_by(Quaternion::from_angle_z(Deg(30.0)));
+#[derive(Debug)]
 pub struct TransformRotate {
-    pub axis: Vector3Cell<f32>,
-    pub angle: Cell<Rad<f32>>,
+    /// 回転軸
+    pub axis: RotateAxis,
+    /// 回転軸まわりの回転角
+    pub angle: RotateAngle,
+    state: Rc<RotateState>,
+}
+
+/// `TransformRotate` の回転軸
+/// 回転角を保ったまま回転軸を取得、変更できる
+#[derive(Debug)]
+pub struct RotateAxis(Rc<RotateState>);
+
+/// `TransformRotate` の回転軸まわりの回転角
+/// 回転軸を保ったまま回転角を取得、変更できる
+#[derive(Debug)]
+pub struct RotateAngle(Rc<RotateState>);
+
+/// `TransformRotate`, `RotateAxis`, `RotateAngle` で共有する回転の状態
+#[derive(Debug)]
+struct RotateState {
+    // 正規化されたクオータニオン
+    quat: Cell<Quaternion<f32>>,
+    // 回転軸
+    // `quat` のベクトル部は常にこの軸と平行になるように保つ。
+    // 回転角が0のときにも、最後に指定された回転軸を覚えておくために使う。
+    axis: StdCell<Vector3<f32>>,
+}
+
+impl RotateState {
+    fn set(&self, quat: Quaternion<f32>) {
+        let quat = quat.normalize();
+        if quat.v.magnitude2() > 0.0 {
+            self.axis.set(quat.v.normalize());
+        }
+        self.quat.set(quat);
+    }
+
+    fn angle(&self) -> Rad<f32> {
+        let quat = self.quat.get();
+        Rad(quat.v.dot(self.axis.get()).atan2(quat.s) * 2.0)
+    }
+
+    fn set_axis_angle(&self, axis: Vector3<f32>, angle: Rad<f32>) {
+        if axis.magnitude2() > 0.0 {
+            self.axis.set(axis.normalize());
+        }
+        self.quat
+            .set(Quaternion::from_axis_angle(self.axis.get(), angle));
+    }
 }
 
 impl TransformRotate {
     fn new() -> Self {
+        TransformRotate::with_state(RotateState {
+            quat: Cell::new(Quaternion::one()),
+            axis: StdCell::new(vec3(1.0, 0.0, 0.0)),
+        })
+    }
+
+    fn with_state(state: RotateState) -> Self {
+        let state = Rc::new(state);
         TransformRotate {
-            axis: Vector3Cell::new(1.0, 0.0, 0.0),
-            angle: Cell::new(Rad(0.0)),
+            axis: RotateAxis(state.clone()),
+            angle: RotateAngle(state.clone()),
+            state,
         }
     }
+
+    pub fn get(&self) -> Quaternion<f32> {
+        self.state.quat.get()
+    }
+
+    /// 回転が最後に変更されたときの世代番号
+    fn generation(&self) -> u64 {
+        self.state.quat.generation()
+    }
+
+    pub fn set(&self, quat: Quaternion<f32>) {
+        self.state.set(quat);
+    }
+
+    /// 回転軸と回転角を設定する。
+    /// 長さ0の回転軸が渡された場合、回転軸は変更しない。
+    pub fn set_axis_angle(&self, axis: Vector3<f32>, angle: Rad<f32>) {
+        self.state.set_axis_angle(axis, angle);
+    }
+
+    pub fn euler(&self) -> Euler<Rad<f32>> {
+        Euler::from(self.get())
+    }
+
+    /// オイラー角で回転を設定する。
+    /// X軸、Y軸、Z軸の順に回転させたものとして扱われる。
+    pub fn set_euler<A>(&self, euler: Euler<A>)
+    where
+        A: Angle<Unitless = f32> + Into<Rad<f32>>,
+    {
+        self.set(Quaternion::from(euler));
+    }
+
+    /// 現在の回転の後に `rotation` を適用する。
+    /// `rotation` は親の座標系における回転として扱われる。
+    pub fn rotate_by(&self, rotation: Quaternion<f32>) {
+        self.set(rotation * self.get());
+    }
+
+    /// 現在の回転から `target` に向けて球面線形補間する。
+    /// `amount` が 0.0 のとき現在の回転、1.0 のとき `target` となる。
+    pub fn slerp(&self, target: Quaternion<f32>, amount: f32) {
+        let current = self.get();
+        let target = target.normalize();
+        // 最短経路で補間するため、逆向きのクオータニオンは反転させる
+        let target = if current.dot(target) < 0.0 {
+            -target
+        } else {
+            target
+        };
+        self.set(current.slerp(target, amount));
+    }
+}
+
+/// Cloneした `TransformRotate` は、生成元と状態を共有しない
+impl Clone for TransformRotate {
+    fn clone(&self) -> Self {
+        TransformRotate::with_state(RotateState {
+            quat: self.state.quat.clone(),
+            axis: self.state.axis.clone(),
+        })
+    }
+}
+
+impl RotateAxis {
+    /// 正規化された回転軸を返す
+    pub fn get(&self) -> Vector3<f32> {
+        self.0.axis.get()
+    }
+
+    /// 回転角を保ったまま回転軸を変更する。
+    /// 長さ0の回転軸が渡された場合は何もしない。
+    pub fn set(&self, x: f32, y: f32, z: f32) {
+        self.0.set_axis_angle(vec3(x, y, z), self.0.angle());
+    }
+}
+
+impl RotateAngle {
+    pub fn get(&self) -> Rad<f32> {
+        self.0.angle()
+    }
+
+    /// 回転軸を保ったまま回転角を変更する
+    pub fn set<A: Into<Rad<f32>>>(&self, angle: A) {
+        self.0.set_axis_angle(self.0.axis.get(), angle.into());
+    }
+
+    /// 回転軸まわりに `angle` だけ回転させる
+    pub fn add<A: Into<Rad<f32>>>(&self, angle: A) {
+        self.set(self.get() + angle.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use cgmath::Deg;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_axis_angle() {
+        let rotate = TransformRotate::new();
+        rotate.axis.set(0.0, 0.0, 2.0);
+        assert_relative_eq!(rotate.axis.get(), vec3(0.0, 0.0, 1.0));
+        assert_relative_eq!(rotate.angle.get(), Rad(0.0));
+
+        rotate.angle.add(Rad(2.0));
+        rotate.angle.add(Rad(2.0));
+        assert_relative_eq!(rotate.axis.get(), vec3(0.0, 0.0, 1.0));
+        assert_relative_eq!(rotate.angle.get(), Rad(4.0), epsilon = 1e-5);
+
+        rotate.axis.set(0.0, 1.0, 0.0);
+        let expected = Matrix4::from_axis_angle(vec3(0.0, 1.0, 0.0), Rad(4.0));
+        assert_relative_eq!(Matrix4::from(rotate.get()), expected, epsilon = 1e-5);
+
+        // Cloneしたものは回転を共有しない
+        let cloned = rotate.clone();
+        cloned.angle.set(Rad(0.0));
+        assert_relative_eq!(rotate.angle.get(), Rad(4.0), epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_rotate_by() {
+        let rotate = TransformRotate::new();
+        rotate.rotate_by(Quaternion::from_angle_x(Deg(180.0)));
+        rotate.rotate_by(Quaternion::from_angle_z(Deg(90.0)));
+
+        // +Y は X軸回転で -Y に、Z軸回転で +X に移る
+        let v = rotate.get().rotate_vector(vec3(0.0, 1.0, 0.0));
+        assert_relative_eq!(v, vec3(1.0, 0.0, 0.0), epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_look_at() {
+        let transform = Transform::new();
+        transform.pos.set(1.0, 0.0, 0.0);
+        transform.look_at(vec3(1.0, 5.0, 0.0), vec3(0.0, 0.0, 1.0));
+
+        let forward = transform.rotate.get().rotate_vector(vec3(0.0, 0.0, 1.0));
+        assert_relative_eq!(forward, vec3(0.0, 1.0, 0.0), epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_look_at_degenerate() {
+        let transform = Transform::new();
+        transform
+            .rotate
+            .set_axis_angle(vec3(0.0, 1.0, 0.0), Rad(1.0));
+        let before = transform.rotate.get();
+
+        // 自身と同じ位置を向こうとしても回転は変わらない
+        transform.look_at(vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0));
+        assert_relative_eq!(transform.rotate.get(), before);
+
+        // `up` が向く方向と平行でも、NaNにならずに向きが決まる
+        transform.look_at(vec3(0.0, 3.0, 0.0), vec3(0.0, 1.0, 0.0));
+        let forward = transform.rotate.get().rotate_vector(vec3(0.0, 0.0, 1.0));
+        assert_relative_eq!(forward, vec3(0.0, 1.0, 0.0), epsilon = 1e-5);
+        assert!(transform.matrix().is_finite());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_slerp() {
+        let rotate = TransformRotate::new();
+        rotate.slerp(Quaternion::from_angle_z(Deg(90.0)), 0.5);
+        assert_relative_eq!(rotate.axis.get(), vec3(0.0, 0.0, 1.0), epsilon = 1e-5);
+        assert_relative_eq!(rotate.angle.get(), Deg(45.0).into(), epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_matrix_cache() {
+        let parent = Rc::new(Transform::new());
+        let child = Transform::new();
+        child.set_parent(Some(&parent));
+        child.pos.set(1.0, 0.0, 0.0);
+        assert_relative_eq!(
+            child.world_matrix(),
+            Matrix4::from_translation(vec3(1.0, 0.0, 0.0))
+        );
+
+        // 親の変更も子のキャッシュに反映される
+        parent.pos.y.set(2.0);
+        assert_relative_eq!(
+            child.world_matrix(),
+            Matrix4::from_translation(vec3(1.0, 2.0, 0.0))
+        );
+        assert_relative_eq!(
+            child.inv_world_matrix().unwrap(),
+            Matrix4::from_translation(vec3(-1.0, -2.0, 0.0))
+        );
+
+        child.set_parent(None);
+        assert_relative_eq!(
+            child.world_matrix(),
+            Matrix4::from_translation(vec3(1.0, 0.0, 0.0))
+        );
+
+        child.scale.z.set(2.0);
+        assert_relative_eq!(
+            child.inv_matrix().unwrap(),
+            child.compute_matrix().invert().unwrap()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_normal_matrix() {
+        // x方向にだけ引き伸ばした斜面の法線は、面と垂直に保たれる
+        let transform = Transform::new();
+        transform.scale.set(2.0, 1.0, 1.0);
+
+        let tangent = (transform.world_matrix() * vec3(1.0, 1.0, 0.0).extend(0.0)).truncate();
+        let normal = transform.normal_matrix() * vec3(1.0, -1.0, 0.0);
+        assert_relative_eq!(tangent.dot(normal), 0.0, epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_zero_scale() {
+        // カードを裏返すアニメーションの途中など、拡大率が0になる軸がある
+        let transform = Transform::new();
+        transform.scale.set(1.0, 1.0, 0.0);
+        assert_eq!(transform.inv_matrix(), None);
+        assert_eq!(transform.inv_world_matrix(), None);
+
+        // XY平面上の面の法線は +Z のまま
+        let normal = transform.normal_matrix() * vec3(0.0, 0.0, 1.0);
+        assert_relative_eq!(normal.normalize(), vec3(0.0, 0.0, 1.0));
+
+        // 拡大率を戻せば逆行列も計算できる
+        transform.scale.z.set(1.0);
+        assert_relative_eq!(transform.inv_matrix().unwrap(), Matrix4::identity());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_parent_keep_world() {
+        let parent = Rc::new(Transform::new());
+        parent.pos.set(1.0, 2.0, 3.0);
+        parent.rotate.axis.set(0.0, 0.0, 1.0);
+        parent.rotate.angle.set(Deg(90.0));
+        parent.scale.set(2.0, 2.0, 2.0);
+
+        let child = Transform::new();
+        child.pos.set(4.0, 5.0, 6.0);
+        child.rotate.axis.set(1.0, 0.0, 0.0);
+        child.rotate.angle.set(Deg(30.0));
+        let world = child.world_matrix();
+
+        child.set_parent_keep_world(Some(&parent));
+        assert_relative_eq!(child.world_matrix(), world, epsilon = 1e-5);
+
+        child.set_parent_keep_world(None);
+        assert_relative_eq!(child.world_matrix(), world, epsilon = 1e-5);
+        assert_relative_eq!(child.pos.get(), vec3(4.0, 5.0, 6.0), epsilon = 1e-5);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_parent_keep_world_mirrored() {
+        // 鏡映を含む親から外しても、world座標系での変換は変わらない
+        let parent = Rc::new(Transform::new());
+        parent.scale.set(-1.0, 1.0, 1.0);
+        let child = Transform::new();
+        child.set_parent(Some(&parent));
+        child.pos.set(1.0, 2.0, 3.0);
+        child.rotate.axis.set(0.0, 1.0, 0.0);
+        child.rotate.angle.set(Deg(30.0));
+        let world = child.world_matrix();
+
+        child.set_parent_keep_world(None);
+        assert_relative_eq!(child.world_matrix(), world, epsilon = 1e-5);
+        assert!(child.scale.x.get() < 0.0);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_parent_keep_world_zero_scale() {
+        // 拡大率が0の親には、位置を保たずにそのまま設定される
+        let parent = Rc::new(Transform::new());
+        parent.scale.set(0.0, 1.0, 1.0);
+        let child = Transform::new();
+        child.pos.set(1.0, 2.0, 3.0);
+
+        child.set_parent_keep_world(Some(&parent));
+        assert!(Rc::ptr_eq(&child.parent().unwrap(), &parent));
+        assert_relative_eq!(child.pos.get(), vec3(1.0, 2.0, 3.0));
+        assert!(child.world_matrix().is_finite());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_parent_cycle() {
+        let grandparent = Rc::new(Transform::new());
+        let parent = Rc::new(Transform::new());
+        assert!(parent.set_parent(Some(&grandparent)));
+        grandparent.pos.set(1.0, 0.0, 0.0);
+
+        // 自身や子孫を親にはできず、親子関係も変わらない
+        assert!(!grandparent.set_parent(Some(&grandparent)));
+        assert!(!grandparent.set_parent(Some(&parent)));
+        assert!(!grandparent.set_parent_keep_world(Some(&parent)));
+        assert!(grandparent.parent().is_none());
+        assert_relative_eq!(grandparent.pos.get(), vec3(1.0, 0.0, 0.0));
+        assert!(Rc::ptr_eq(&parent.parent().unwrap(), &grandparent));
+    }
 }
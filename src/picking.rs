@@ -0,0 +1,72 @@
+use crate::{ray::Ray, scene::ObjectId, Object, Scene};
+use cgmath::{prelude::*, Vector3};
+use std::cmp::Ordering;
+
+/// ピッキングの結果
+#[derive(Debug)]
+pub struct Hit {
+    /// Rayと交差したObjectのID
+    pub id: ObjectId,
+    /// Rayと交差したObject
+    pub object: Object,
+    /// Rayの始点から交点までの、world座標系での距離
+    pub distance: f32,
+    /// world座標系での交点
+    pub point: Vector3<f32>,
+    /// 交差した三角形の、`Mesh::indexes` におけるインデックス
+    pub triangle_index: usize,
+    /// 交差した三角形の各頂点のworld座標
+    pub triangle: [Vector3<f32>; 3],
+}
+
+/// `ray` と交差するObjectのうち、最もRayの始点に近いものを返す
+pub fn pick(scene: &Scene, ray: &Ray) -> Option<Hit> {
+    scene
+        .iter()
+        .filter_map(|(id, object)| intersect_object(id, object, ray))
+        .min_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        })
+}
+
+fn intersect_object(id: ObjectId, object: &Object, ray: &Ray) -> Option<Hit> {
+    let mesh = &object.mesh;
+
+    // Local座標系で判定する
+    // 拡大率が0の軸がありLocal座標系に変換できないObjectは、体積を持たないので交差しないものとする
+    let local_ray = ray.transform(object.transform.inv_world_matrix()?);
+
+    // まずはバウンディングボックスで大まかに判定する
+    mesh.bounds?.intersect_ray(&local_ray)?;
+
+    let (triangle_index, local_distance) = (0..mesh.indexes.count())
+        .filter_map(|idx| {
+            let [a, b, c] = mesh.triangle(idx)?;
+            let t = local_ray.intersect_triangle(a, b, c)?;
+            Some((idx, t))
+        })
+        // 変換行列にNaNが含まれる場合などは、交差しなかったものとする
+        .filter(|(_, t)| t.is_finite())
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+
+    let world_matrix = object.transform.world_matrix();
+    let to_world = |v: Vector3<f32>| (world_matrix * v.extend(1.0)).truncate();
+
+    let point = to_world(local_ray.at(local_distance));
+    let [a, b, c] = mesh.triangle(triangle_index)?;
+    let distance = (point - ray.origin).dot(ray.dir);
+    if !distance.is_finite() {
+        return None;
+    }
+
+    Some(Hit {
+        id,
+        object: object.shared_clone(),
+        distance,
+        point,
+        triangle_index,
+        triangle: [to_world(a), to_world(b), to_world(c)],
+    })
+}
@@ -1,35 +1,113 @@
+// シャドウマップから復元した深度を比較するため、使える場合は高い精度で計算する
+// mediumpでは、RGBAに分割して格納した深度の精度が失われる
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
 precision mediump float;
+#endif
 
-uniform mat4 invMMatrix; // モデル座標変換行列の逆行列
-uniform int  lightType; // 0: 光源なし, 1: 平行光源, 2: 点光源
-uniform vec3 lightVal; // 平行光源のときdirection, 点光源のときposition
+uniform int  lightCount; // 有効な光源の数
+uniform int  lightTypes[MAX_LIGHTS]; // 1: 平行光源, 2: 点光源, 3: スポットライト
+uniform vec3 lightVals[MAX_LIGHTS]; // 平行光源のときdirection, 点光源・スポットライトのときposition
+uniform vec3 lightColors[MAX_LIGHTS]; // 光の色に強さを掛け合わせたもの
+uniform vec3 lightAttenuations[MAX_LIGHTS]; // 点光源・スポットライトの減衰係数 (constant, linear, quadratic)
+uniform vec3 lightDirections[MAX_LIGHTS]; // スポットライトの向き
+uniform vec2 lightCones[MAX_LIGHTS]; // スポットライトの (cos(inner_angle), cos(outer_angle))
 uniform vec3 eyeDirection;
 uniform vec4 ambientColor;
+uniform vec4 baseColor; // 頂点の色に掛け合わせるMaterialの色
+uniform float shininess; // 鏡面反射のハイライトの鋭さ
+uniform float specularStrength; // 鏡面反射の強さ
+uniform vec3 emissive; // 自ら発する色
+uniform bool unlit; // trueのとき光の計算を行わない
+uniform int  shadowLight; // 影を落とす光源のインデックス。影を受けない場合は-1
+uniform mat4 shadowMatrix; // World座標を、影を落とす光源から見たクリップ座標に変換する行列
+uniform sampler2D shadowMap; // 光源から見た深度を格納したテクスチャ
+uniform float shadowBias; // シャドウアクネを防ぐために深度の比較に加えるオフセット
 
 varying vec3 vPosition;     // World座標系での位置
-varying vec3 vNormal;       // Local座標系での法線ベクトル
+varying vec3 vNormal; // World座標系での法線ベクトル
 varying vec4 vColor;
 
-vec3 invLight() {
-  // lightType == 0 のとき、このパスを通らないようにする
+// World座標系での、光源へ向かう単位ベクトル
+vec3 invLight(int lightType, vec3 lightVal) {
   vec3 lightDir = (lightType == 1) ? lightVal : vPosition - lightVal;
-    return normalize(invMMatrix * vec4(-lightDir, 0.0)).xyz;
+  return normalize(-lightDir);
 }
 
-vec4 diffuse() {
-    float diffuseVal = clamp(dot(vNormal, invLight()), 0.0, 1.0);
-    return vec4(vec3(diffuseVal), 1.0);
+// 光源からの距離による減衰率
+float attenuation(int lightType, vec3 lightVal, vec3 coeffs, vec3 worldPos) {
+  if (lightType == 1) {
+    return 1.0;
+  }
+  float d = distance(worldPos, lightVal);
+  return 1.0 / (coeffs.x + coeffs.y * d + coeffs.z * d * d);
 }
 
-vec4 specular() {
-    vec3  invEye    = normalize(invMMatrix * vec4(-eyeDirection, 0.0)).xyz;
-    vec3  halfLE    = normalize(invLight() + invEye);
-    float specularVal = pow(clamp(dot(vNormal, halfLE), 0.0, 1.0), 50.0);
-    return vec4(vec3(specularVal), 0.0);
+// packDepthで格納された深度を復元する
+float unpackDepth(vec4 rgba) {
+  return dot(rgba, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0));
+}
+
+// 影になっていなければ1.0、影になっていれば0.0を返す
+// shadowCoordは光源から見たクリップ座標
+float shadowFactor(vec4 shadowCoord) {
+  if (shadowCoord.w <= 0.0) {
+    return 1.0;
+  }
+  vec3 coord = shadowCoord.xyz / shadowCoord.w * 0.5 + 0.5;
+  if (coord.x < 0.0 || coord.x > 1.0 || coord.y < 0.0 || coord.y > 1.0 || coord.z > 1.0) {
+    // シャドウマップの範囲外は影にしない
+    return 1.0;
+  }
+  float depth = unpackDepth(texture2D(shadowMap, coord.xy));
+  return (coord.z - shadowBias > depth) ? 0.0 : 1.0;
+}
+
+// スポットライトの光軸からの角度による減衰率
+float spotFactor(int lightType, vec3 lightVal, vec3 spotDir, vec2 cone, vec3 worldPos) {
+  if (lightType != 3) {
+    return 1.0;
+  }
+  float cosTheta = dot(normalize(worldPos - lightVal), normalize(spotDir));
+  if (cone.x <= cone.y) {
+    return step(cone.y, cosTheta);
+  }
+  return smoothstep(cone.y, cone.x, cosTheta);
 }
 
 void main(void) {
-  gl_FragColor = (lightType == 0)
-    ? vColor + ambientColor
-    : vColor * diffuse() + specular() + ambientColor;
+  vec3 invEye = normalize(-eyeDirection);
+  vec3 normal = normalize(vNormal);
+
+  vec3 diffuse = vec3(0.0);
+  vec3 specular = vec3(0.0);
+  for (int i = 0; i < MAX_LIGHTS; i++) {
+    if (i >= lightCount) {
+      break;
+    }
+    vec3 light = invLight(lightTypes[i], lightVals[i]);
+    vec3 radiance = lightColors[i]
+      * attenuation(lightTypes[i], lightVals[i], lightAttenuations[i], vPosition)
+      * spotFactor(lightTypes[i], lightVals[i], lightDirections[i], lightCones[i], vPosition);
+    if (i == shadowLight) {
+      radiance *= shadowFactor(shadowMatrix * vec4(vPosition, 1.0));
+    }
+    vec3 halfLE = normalize(light + invEye);
+    diffuse += radiance * clamp(dot(normal, light), 0.0, 1.0);
+    specular += radiance * specularStrength
+      * pow(clamp(dot(normal, halfLE), 0.0, 1.0), shininess);
+  }
+
+  // 環境光のアルファ値は、Materialの不透明度に影響させない
+  vec4 ambient = vec4(ambientColor.rgb, 0.0);
+  vec4 materialColor = vColor * baseColor;
+  if (unlit) {
+    gl_FragColor = materialColor;
+  } else if (lightCount == 0) {
+    gl_FragColor = materialColor + ambient;
+  } else {
+    gl_FragColor = materialColor * vec4(diffuse, 1.0) + vec4(specular, 0.0) + ambient;
+  }
+  gl_FragColor += vec4(emissive, 0.0);
 }
@@ -1,11 +1,13 @@
-use cgmath::{Matrix4, Vector3, Vector4};
+use super::with_defines;
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
 use napier_webgl::{
     context,
+    error::Error,
     program::{Attribute, GlProgram, ParamsBase, ParamsVisitor, Uniform},
     shader::{FragmentShader, VertexShader},
+    texture::GlTextureUnit,
     vec::StepVec,
 };
-use wasm_bindgen::prelude::*;
 
 pub struct BasicProgram {
     gl: GlProgram<BasicParams>,
@@ -13,9 +15,9 @@ pub struct BasicProgram {
 
 impl BasicProgram {
     /// フォンシェーディング版のBasicProgramを生成する
-    pub fn phong() -> Result<Self, JsValue> {
-        let vert_shader = VertexShader::compile(include_str!("basic-phong.vert"))?;
-        let frag_shader = FragmentShader::compile(include_str!("basic-phong.frag"))?;
+    pub fn phong() -> Result<Self, Error> {
+        let vert_shader = VertexShader::compile(&with_defines(include_str!("basic-phong.vert")))?;
+        let frag_shader = FragmentShader::compile(&with_defines(include_str!("basic-phong.frag")))?;
 
         let gl = GlProgram::<BasicParams>::new(vert_shader, frag_shader)?;
 
@@ -23,19 +25,16 @@ impl BasicProgram {
     }
 
     /// グーローシェーディング版のBasicProgramを生成する
-    pub fn gouraud() -> Result<Self, JsValue> {
-        let vert_shader = VertexShader::compile(include_str!("basic-gouraud.vert"))?;
-        let frag_shader = FragmentShader::compile(include_str!("basic-gouraud.frag"))?;
+    pub fn gouraud() -> Result<Self, Error> {
+        let vert_shader = VertexShader::compile(&with_defines(include_str!("basic-gouraud.vert")))?;
+        let frag_shader =
+            FragmentShader::compile(&with_defines(include_str!("basic-gouraud.frag")))?;
 
         let gl = GlProgram::<BasicParams>::new(vert_shader, frag_shader)?;
 
         Ok(BasicProgram { gl })
     }
 
-    pub(crate) fn params(&self) -> &BasicParams {
-        &self.gl.params
-    }
-
     pub(crate) fn params_mut(&mut self) -> &mut BasicParams {
         &mut self.gl.params
     }
@@ -52,17 +51,31 @@ pub struct BasicParams {
     pub color: Attribute<StepVec<Vector4<f32>>>,
     pub mvp_matrix: Uniform<Matrix4<f32>>,
     pub m_matrix: Uniform<Matrix4<f32>>,
+    pub normal_matrix: Uniform<Matrix3<f32>>,
 
     // for fragment shader
-    pub inv_m_matrix: Uniform<Matrix4<f32>>,
-    pub light_type: Uniform<i32>,
-    pub light_val: Uniform<Vector3<f32>>,
+    pub light_count: Uniform<i32>,
+    pub light_types: Uniform<Vec<i32>>,
+    pub light_vals: Uniform<Vec<Vector3<f32>>>,
+    pub light_colors: Uniform<Vec<Vector3<f32>>>,
+    pub light_attenuations: Uniform<Vec<Vector3<f32>>>,
+    pub light_directions: Uniform<Vec<Vector3<f32>>>,
+    pub light_cones: Uniform<Vec<Vector2<f32>>>,
     pub eye_direction: Uniform<Vector3<f32>>,
+    pub shadow_light: Uniform<i32>,
+    pub shadow_matrix: Uniform<Matrix4<f32>>,
+    pub shadow_map: Uniform<GlTextureUnit>,
+    pub shadow_bias: Uniform<f32>,
     pub ambient_color: Uniform<Vector4<f32>>,
+    pub base_color: Uniform<Vector4<f32>>,
+    pub shininess: Uniform<f32>,
+    pub specular_strength: Uniform<f32>,
+    pub emissive: Uniform<Vector3<f32>>,
+    pub unlit: Uniform<bool>,
 }
 
 impl ParamsBase for BasicParams {
-    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, JsValue> {
+    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, Error> {
         Ok(BasicParams {
             // for vertex shader
             position: visitor.visit_attr("position")?,
@@ -70,13 +83,27 @@ impl ParamsBase for BasicParams {
             color: visitor.visit_attr("color")?,
             mvp_matrix: visitor.visit_uniform("mvpMatrix")?,
             m_matrix: visitor.visit_uniform("mMatrix")?,
+            normal_matrix: visitor.visit_uniform("normalMatrix")?,
 
             // for fragment shader
-            inv_m_matrix: visitor.visit_uniform("invMMatrix")?,
-            light_type: visitor.visit_uniform("lightType")?,
-            light_val: visitor.visit_uniform("lightVal")?,
+            light_count: visitor.visit_uniform("lightCount")?,
+            light_types: visitor.visit_uniform("lightTypes")?,
+            light_vals: visitor.visit_uniform("lightVals")?,
+            light_colors: visitor.visit_uniform("lightColors")?,
+            light_attenuations: visitor.visit_uniform("lightAttenuations")?,
+            light_directions: visitor.visit_uniform("lightDirections")?,
+            light_cones: visitor.visit_uniform("lightCones")?,
             eye_direction: visitor.visit_uniform("eyeDirection")?,
+            shadow_light: visitor.visit_uniform("shadowLight")?,
+            shadow_matrix: visitor.visit_uniform("shadowMatrix")?,
+            shadow_map: visitor.visit_uniform("shadowMap")?,
+            shadow_bias: visitor.visit_uniform("shadowBias")?,
             ambient_color: visitor.visit_uniform("ambientColor")?,
+            base_color: visitor.visit_uniform("baseColor")?,
+            shininess: visitor.visit_uniform("shininess")?,
+            specular_strength: visitor.visit_uniform("specularStrength")?,
+            emissive: visitor.visit_uniform("emissive")?,
+            unlit: visitor.visit_uniform("unlit")?,
         })
     }
 }
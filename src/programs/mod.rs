@@ -1,5 +1,16 @@
+use crate::light::MAX_LIGHTS;
+
 pub mod basic;
+pub mod picking;
+pub mod shadow;
 pub mod texture;
 
 pub use basic::{BasicParams, BasicProgram};
+pub use picking::{PickingParams, PickingProgram};
+pub use shadow::{ShadowParams, ShadowProgram};
 pub use texture::{TextureParams, TextureProgram};
+
+/// シェーダーのソースに、共通で使用するマクロ定義を付け加える
+pub(crate) fn with_defines(src: &str) -> String {
+    format!("#define MAX_LIGHTS {}\n{}", MAX_LIGHTS, src)
+}
@@ -0,0 +1,7 @@
+precision mediump float;
+
+uniform vec4 idColor; // Objectを識別するための色
+
+void main(void) {
+  gl_FragColor = idColor;
+}
@@ -0,0 +1,59 @@
+use cgmath::{Matrix4, Vector3, Vector4};
+use napier_webgl::{
+    context,
+    error::Error,
+    program::{Attribute, GlProgram, ParamsBase, ParamsVisitor, Uniform},
+    shader::{FragmentShader, VertexShader},
+    vec::StepVec,
+};
+
+/// Objectごとに異なる単色で描画するためのProgram
+/// 描画結果の色から、その位置に写っているObjectを識別するのに使う
+pub struct PickingProgram {
+    gl: GlProgram<PickingParams>,
+}
+
+impl PickingProgram {
+    pub fn new() -> Result<Self, Error> {
+        let vert_shader = VertexShader::compile(include_str!("picking.vert"))?;
+        let frag_shader = FragmentShader::compile(include_str!("picking.frag"))?;
+
+        let gl = GlProgram::<PickingParams>::new(vert_shader, frag_shader)?;
+
+        Ok(PickingProgram { gl })
+    }
+
+    pub(crate) fn params(&self) -> &PickingParams {
+        &self.gl.params
+    }
+
+    pub(crate) fn params_mut(&mut self) -> &mut PickingParams {
+        &mut self.gl.params
+    }
+
+    pub(crate) fn switch(&self) {
+        context::with(|ctx| ctx.switch_program(&self.gl))
+    }
+}
+
+pub struct PickingParams {
+    // for vertex shader
+    pub position: Attribute<StepVec<Vector3<f32>>>,
+    pub mvp_matrix: Uniform<Matrix4<f32>>,
+
+    // for fragment shader
+    pub id_color: Uniform<Vector4<f32>>,
+}
+
+impl ParamsBase for PickingParams {
+    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, Error> {
+        Ok(PickingParams {
+            // for vertex shader
+            position: visitor.visit_attr("position")?,
+            mvp_matrix: visitor.visit_uniform("mvpMatrix")?,
+
+            // for fragment shader
+            id_color: visitor.visit_uniform("idColor")?,
+        })
+    }
+}
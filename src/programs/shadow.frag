@@ -0,0 +1,15 @@
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+// 0 ~ 1 の深度を、精度を落とさないようにRGBAの各チャンネルへ分割して格納する
+vec4 packDepth(float depth) {
+  vec4 r = fract(depth * vec4(1.0, 255.0, 65025.0, 16581375.0));
+  return r - r.yzww * vec4(1.0 / 255.0, 1.0 / 255.0, 1.0 / 255.0, 0.0);
+}
+
+void main(void) {
+  gl_FragColor = packDepth(gl_FragCoord.z);
+}
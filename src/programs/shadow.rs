@@ -0,0 +1,53 @@
+use cgmath::{Matrix4, Vector3};
+use napier_webgl::{
+    context,
+    error::Error,
+    program::{Attribute, GlProgram, ParamsBase, ParamsVisitor, Uniform},
+    shader::{FragmentShader, VertexShader},
+    vec::StepVec,
+};
+
+/// 光源から見た深度をシャドウマップに書き込むためのProgram
+/// 深度はRGBAの各チャンネルに分割して格納される
+pub struct ShadowProgram {
+    gl: GlProgram<ShadowParams>,
+}
+
+impl ShadowProgram {
+    pub fn new() -> Result<Self, Error> {
+        let vert_shader = VertexShader::compile(include_str!("shadow.vert"))?;
+        let frag_shader = FragmentShader::compile(include_str!("shadow.frag"))?;
+
+        let gl = GlProgram::<ShadowParams>::new(vert_shader, frag_shader)?;
+
+        Ok(ShadowProgram { gl })
+    }
+
+    pub(crate) fn params(&self) -> &ShadowParams {
+        &self.gl.params
+    }
+
+    pub(crate) fn params_mut(&mut self) -> &mut ShadowParams {
+        &mut self.gl.params
+    }
+
+    pub(crate) fn switch(&self) {
+        context::with(|ctx| ctx.switch_program(&self.gl))
+    }
+}
+
+pub struct ShadowParams {
+    // for vertex shader
+    pub position: Attribute<StepVec<Vector3<f32>>>,
+    pub mvp_matrix: Uniform<Matrix4<f32>>,
+}
+
+impl ParamsBase for ShadowParams {
+    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, Error> {
+        Ok(ShadowParams {
+            // for vertex shader
+            position: visitor.visit_attr("position")?,
+            mvp_matrix: visitor.visit_uniform("mvpMatrix")?,
+        })
+    }
+}
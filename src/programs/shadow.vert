@@ -0,0 +1,7 @@
+attribute vec3 position;
+
+uniform   mat4 mvpMatrix; // 光源から見たmvp行列
+
+void main(void) {
+  gl_Position = mvpMatrix * vec4(position, 1.0);
+}
@@ -1,10 +1,42 @@
+// シャドウマップから復元した深度を比較するため、使える場合は高い精度で計算する
+// mediumpでは、RGBAに分割して格納した深度の精度が失われる
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
 precision mediump float;
+#endif
 
 uniform sampler2D uTexture;
+uniform vec3 emissive; // 自ら発する色。テクスチャの色に関わらず加える
+uniform sampler2D shadowMap; // 光源から見た深度を格納したテクスチャ
+uniform float shadowBias; // シャドウアクネを防ぐために深度の比較に加えるオフセット
 
 varying vec4 vColor;
+varying vec4 vShadowedColor;
+varying vec4 vShadowCoord;
 varying vec2 vTexCoord;
 
+// packDepthで格納された深度を復元する
+float unpackDepth(vec4 rgba) {
+  return dot(rgba, vec4(1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0));
+}
+
+// 影になっていなければ1.0、影になっていれば0.0を返す
+// shadowCoordは光源から見たクリップ座標
+float shadowFactor(vec4 shadowCoord) {
+  if (shadowCoord.w <= 0.0) {
+    return 1.0;
+  }
+  vec3 coord = shadowCoord.xyz / shadowCoord.w * 0.5 + 0.5;
+  if (coord.x < 0.0 || coord.x > 1.0 || coord.y < 0.0 || coord.y > 1.0 || coord.z > 1.0) {
+    // シャドウマップの範囲外は影にしない
+    return 1.0;
+  }
+  float depth = unpackDepth(texture2D(shadowMap, coord.xy));
+  return (coord.z - shadowBias > depth) ? 0.0 : 1.0;
+}
+
 void main(void) {
-  gl_FragColor = vColor * texture2D(uTexture, vTexCoord);
+  vec4 color = vColor + vShadowedColor * shadowFactor(vShadowCoord);
+  gl_FragColor = color * texture2D(uTexture, vTexCoord) + vec4(emissive, 0.0);
 }
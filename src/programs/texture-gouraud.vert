@@ -5,41 +5,102 @@ attribute vec2 texCoord;
 
 uniform   mat4 mvpMatrix;
 uniform   mat4 mMatrix; // モデル座標変換行列
-uniform   mat4 invMMatrix; // mMatrixの逆行列。WebGL1.0ではinverse関数をサポートしていない
-uniform   int  lightType;  // 0: 光源なし, 1: 平行光源, 2: 点光源
-uniform   vec3 lightVal;   // 平行光源のときdirection, 点光源のときposition
+uniform   mat3 normalMatrix; // 法線ベクトルをWorld座標系に変換する行列
+uniform   int  lightCount; // 有効な光源の数
+uniform   int  lightTypes[MAX_LIGHTS]; // 1: 平行光源, 2: 点光源, 3: スポットライト
+uniform   vec3 lightVals[MAX_LIGHTS];  // 平行光源のときdirection, 点光源・スポットライトのときposition
+uniform   vec3 lightColors[MAX_LIGHTS]; // 光の色に強さを掛け合わせたもの
+uniform   vec3 lightAttenuations[MAX_LIGHTS]; // 点光源・スポットライトの減衰係数 (constant, linear, quadratic)
+uniform   vec3 lightDirections[MAX_LIGHTS]; // スポットライトの向き
+uniform   vec2 lightCones[MAX_LIGHTS]; // スポットライトの (cos(inner_angle), cos(outer_angle))
 uniform   vec3 eyeDirection; // カメラの視線方向
 uniform   vec4 ambientColor; // 環境光
+uniform   vec4 baseColor; // 頂点の色に掛け合わせるMaterialの色
+uniform   float shininess; // 鏡面反射のハイライトの鋭さ
+uniform   float specularStrength; // 鏡面反射の強さ
+uniform   bool unlit; // trueのとき光の計算を行わない
+uniform   int  shadowLight; // 影を落とす光源のインデックス。影を受けない場合は-1
+uniform   mat4 shadowMatrix; // World座標を、影を落とす光源から見たクリップ座標に変換する行列
 
-varying   vec4 vColor; // 各頂点における色
+varying   vec4 vColor; // 影を落とす光源以外による色
+varying   vec4 vShadowedColor; // 影を落とす光源による色。フラグメントシェーダーで影の割合を掛ける
+varying   vec4 vShadowCoord; // 影を落とす光源から見たクリップ座標
 varying   vec2 vTexCoord; // 各頂点におけるテクスチャの座標
 
-vec3 invLight() {
-  // World座標系における頂点座標
-  vec3 worldPos = (mMatrix * vec4(position, 1.0)).xyz;
+// World座標系での、光源へ向かう単位ベクトル
+vec3 invLight(int lightType, vec3 lightVal, vec3 worldPos) {
   vec3 lightDir = (lightType == 1) ? lightVal : worldPos - lightVal;
-  return normalize(invMMatrix * vec4(-lightDir, 0.0)).xyz;
+  return normalize(-lightDir);
 }
 
-vec4 diffuse() {
-  float diffuseVal = clamp(dot(normal, invLight()), 0.0, 1.0);
-  return vec4(vec3(diffuseVal), 1.0);
+// 光源からの距離による減衰率
+float attenuation(int lightType, vec3 lightVal, vec3 coeffs, vec3 worldPos) {
+  if (lightType == 1) {
+    return 1.0;
+  }
+  float d = distance(worldPos, lightVal);
+  return 1.0 / (coeffs.x + coeffs.y * d + coeffs.z * d * d);
 }
 
-vec4 specular() {
-  vec3 invEye = normalize(invMMatrix * vec4(-eyeDirection, 0.0)).xyz;
-  vec3 halfLE = normalize(invLight() + invEye);
-  float specularVal = pow(clamp(dot(normal, halfLE), 0.0, 1.0), 50.0);
-  return vec4(vec3(specularVal), 0.0);
+// スポットライトの光軸からの角度による減衰率
+float spotFactor(int lightType, vec3 lightVal, vec3 spotDir, vec2 cone, vec3 worldPos) {
+  if (lightType != 3) {
+    return 1.0;
+  }
+  float cosTheta = dot(normalize(worldPos - lightVal), normalize(spotDir));
+  if (cone.x <= cone.y) {
+    return step(cone.y, cosTheta);
+  }
+  return smoothstep(cone.y, cone.x, cosTheta);
 }
 
 void main(void) {
-  // World座標系での頂点座標
   gl_Position = mvpMatrix * vec4(position, 1.0);
+  // World座標系での頂点座標
+  vec3 worldPos = (mMatrix * vec4(position, 1.0)).xyz;
+
+  vec3 invEye = normalize(-eyeDirection);
+  vec3 worldNormal = normalize(normalMatrix * normal);
+
+  vec3 diffuse = vec3(0.0);
+  vec3 specular = vec3(0.0);
+  vec3 shadowedDiffuse = vec3(0.0);
+  vec3 shadowedSpecular = vec3(0.0);
+  for (int i = 0; i < MAX_LIGHTS; i++) {
+    if (i >= lightCount) {
+      break;
+    }
+    vec3 light = invLight(lightTypes[i], lightVals[i], worldPos);
+    vec3 radiance = lightColors[i]
+      * attenuation(lightTypes[i], lightVals[i], lightAttenuations[i], worldPos)
+      * spotFactor(lightTypes[i], lightVals[i], lightDirections[i], lightCones[i], worldPos);
+    vec3 halfLE = normalize(light + invEye);
+    vec3 d = radiance * clamp(dot(worldNormal, light), 0.0, 1.0);
+    vec3 s = radiance * specularStrength
+      * pow(clamp(dot(worldNormal, halfLE), 0.0, 1.0), shininess);
+    if (i == shadowLight) {
+      shadowedDiffuse += d;
+      shadowedSpecular += s;
+    } else {
+      diffuse += d;
+      specular += s;
+    }
+  }
 
-  vColor = (lightType == 0)
-    ? color + ambientColor
-    : color * diffuse() + specular() + ambientColor;
+  // 環境光のアルファ値は、Materialの不透明度に影響させない
+  vec4 ambient = vec4(ambientColor.rgb, 0.0);
+  vec4 materialColor = color * baseColor;
+  if (unlit) {
+    vColor = materialColor;
+  } else if (lightCount == 0) {
+    vColor = materialColor + ambient;
+  } else {
+    vColor = materialColor * vec4(diffuse, 1.0) + vec4(specular, 0.0) + ambient;
+  }
+  vShadowedColor = unlit
+    ? vec4(0.0)
+    : materialColor * vec4(shadowedDiffuse, 0.0) + vec4(shadowedSpecular, 0.0);
+  vShadowCoord = shadowMatrix * vec4(worldPos, 1.0);
 
   vTexCoord = texCoord;
 }
@@ -1,13 +1,13 @@
-use super::BasicParams;
+use super::{with_defines, BasicParams};
 use cgmath::Vector2;
 use napier_webgl::{
     context,
+    error::Error,
     program::{Attribute, GlProgram, ParamsBase, ParamsVisitor, Uniform},
     shader::{FragmentShader, VertexShader},
     texture::GlTextureUnit,
     vec::StepVec,
 };
-use wasm_bindgen::prelude::*;
 
 pub struct TextureProgram {
     gl: GlProgram<TextureParams>,
@@ -15,9 +15,10 @@ pub struct TextureProgram {
 
 impl TextureProgram {
     /// フォンシェーディング版のTextureProgramを生成する
-    pub fn phong() -> Result<Self, JsValue> {
-        let vert_shader = VertexShader::compile(include_str!("texture-phong.vert"))?;
-        let frag_shader = FragmentShader::compile(include_str!("texture-phong.frag"))?;
+    pub fn phong() -> Result<Self, Error> {
+        let vert_shader = VertexShader::compile(&with_defines(include_str!("texture-phong.vert")))?;
+        let frag_shader =
+            FragmentShader::compile(&with_defines(include_str!("texture-phong.frag")))?;
 
         let gl = GlProgram::<TextureParams>::new(vert_shader, frag_shader)?;
 
@@ -25,19 +26,17 @@ impl TextureProgram {
     }
 
     /// グーローシェーディング版のTextureProgramを生成する
-    pub fn gouraud() -> Result<Self, JsValue> {
-        let vert_shader = VertexShader::compile(include_str!("texture-gouraud.vert"))?;
-        let frag_shader = FragmentShader::compile(include_str!("texture-gouraud.frag"))?;
+    pub fn gouraud() -> Result<Self, Error> {
+        let vert_shader =
+            VertexShader::compile(&with_defines(include_str!("texture-gouraud.vert")))?;
+        let frag_shader =
+            FragmentShader::compile(&with_defines(include_str!("texture-gouraud.frag")))?;
 
         let gl = GlProgram::<TextureParams>::new(vert_shader, frag_shader)?;
 
         Ok(TextureProgram { gl })
     }
 
-    pub(crate) fn params(&self) -> &TextureParams {
-        &self.gl.params
-    }
-
     pub(crate) fn params_mut(&mut self) -> &mut TextureParams {
         &mut self.gl.params
     }
@@ -54,7 +53,7 @@ pub struct TextureParams {
 }
 
 impl ParamsBase for TextureParams {
-    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, JsValue> {
+    fn from_visitor<'a>(visitor: &mut ParamsVisitor<'a>) -> Result<Self, Error> {
         Ok(TextureParams {
             basic: BasicParams::from_visitor(visitor)?,
             tex_coord: visitor.visit_attr("texCoord")?,
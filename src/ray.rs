@@ -0,0 +1,104 @@
+use cgmath::{prelude::*, Matrix4, Vector3};
+
+/// 始点と向きで表される半直線
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    /// 始点
+    pub origin: Vector3<f32>,
+    /// 向き
+    /// 常に正規化されている
+    pub dir: Vector3<f32>,
+}
+
+impl Ray {
+    /// 新しいRayを生成する
+    /// `dir` は正規化される
+    pub fn new(origin: Vector3<f32>, dir: Vector3<f32>) -> Ray {
+        Ray {
+            origin,
+            dir: dir.normalize(),
+        }
+    }
+
+    /// 始点から `distance` だけ進んだ位置を返す
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.dir * distance
+    }
+
+    /// 変換行列 `m` を適用したRayを返す
+    pub fn transform(&self, m: Matrix4<f32>) -> Ray {
+        let origin = m * self.origin.extend(1.0);
+        let dir = m * self.dir.extend(0.0);
+        Ray::new(origin.truncate() / origin.w, dir.truncate())
+    }
+
+    /// 三角形 `a`, `b`, `c` と交差する場合、Rayの始点から交点までの距離を返す。
+    /// 三角形の表裏は区別しない。
+    pub fn intersect_triangle(
+        &self,
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        c: Vector3<f32>,
+    ) -> Option<f32> {
+        // Möller–Trumbore の交差判定
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = self.dir.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < f32::EPSILON {
+            // Rayと三角形が平行
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.dir.dot(q) * inv_det;
+        if v < 0.0 || 1.0 < u + v {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inv_det;
+        if t < 0.0 {
+            return None;
+        }
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use cgmath::vec3;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_intersect_triangle() {
+        let ray = Ray::new(vec3(0.2, 0.2, 5.0), vec3(0.0, 0.0, -2.0));
+        let (a, b, c) = (
+            vec3(0.0, 0.0, 1.0),
+            vec3(1.0, 0.0, 1.0),
+            vec3(0.0, 1.0, 1.0),
+        );
+        assert_relative_eq!(ray.intersect_triangle(a, b, c).unwrap(), 4.0);
+        // 裏側からも交差する
+        assert_relative_eq!(ray.intersect_triangle(a, c, b).unwrap(), 4.0);
+
+        let miss = Ray::new(vec3(0.8, 0.8, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(miss.intersect_triangle(a, b, c), None);
+
+        let behind = Ray::new(vec3(0.2, 0.2, 0.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(behind.intersect_triangle(a, b, c), None);
+
+        // 退化した変換行列から作られたRayは交差しない
+        let nan = Ray::new(vec3(f32::NAN, 0.2, 5.0), vec3(0.0, 0.0, -1.0));
+        assert_eq!(nan.intersect_triangle(a, b, c), None);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    camera::Camera,
+    material::{Material, ShadingModel},
+    meshes::Mesh,
+    object::Object,
+    scene::Scene,
+    stats::RenderStats,
+    texture::Texture,
+};
+use cgmath::{prelude::*, Vector3};
+use std::{cmp::Ordering, rc::Rc};
+
+/// 直前の `Renderer::render` で、描画順の並び替えと状態の追跡によって省略できた状態変更の回数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SavedStateChanges {
+    /// Programの切り替え
+    pub program_switches: usize,
+    /// テクスチャのバインド
+    pub texture_binds: usize,
+    /// 頂点バッファ、インデックスバッファのバインド
+    pub mesh_binds: usize,
+    /// uniform変数の送信
+    /// フレーム内で共通の値（光源など）と、Materialの値をそれぞれ一組として数える
+    pub uniform_uploads: usize,
+}
+
+/// Objectの描画に使うProgramの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct ProgramKey {
+    pub textured: bool,
+    /// グーローシェーディング版のProgramを使うかどうか
+    pub gouraud: bool,
+}
+
+impl ProgramKey {
+    pub fn shading(self) -> ShadingModel {
+        if self.gouraud {
+            ShadingModel::Gouraud
+        } else {
+            ShadingModel::Phong
+        }
+    }
+}
+
+/// 描画キューに積まれる、1つのObjectの描画
+pub(crate) struct DrawItem<'a> {
+    pub object: &'a Object,
+    pub program: ProgramKey,
+    pub texture: Option<Rc<Texture>>,
+}
+
+impl<'a> DrawItem<'a> {
+    fn new(object: &'a Object) -> Self {
+        let texture = object_texture(object);
+        let program = ProgramKey {
+            textured: texture.is_some(),
+            // Unlitは光の計算を行わないため、負荷の低いグーローシェーディング版を使う
+            gouraud: object.material.shading.get() != ShadingModel::Phong,
+        };
+        DrawItem {
+            object,
+            program,
+            texture,
+        }
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.object.material.color_vec4().w < 1.0
+            || self.object.mesh.has_transparent_vertex
+            || self.texture.as_ref().is_some_and(|t| t.has_alpha())
+    }
+}
+
+/// 1フレーム分の描画キュー
+pub(crate) struct RenderQueue<'a> {
+    /// Program、テクスチャ、Meshの順に並べた不透明なObject
+    pub opaques: Vec<DrawItem<'a>>,
+    /// カメラから遠い順に並べた半透明なObject
+    pub transparents: Vec<DrawItem<'a>>,
+    /// カメラに写らないため、キューに積まなかったObjectの数
+    pub culled: usize,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new(scene: &'a Scene, camera: &Camera) -> Self {
+        let frustum = camera.frustum();
+        let objects = scene.objects();
+        let (mut transparents, mut opaques): (Vec<_>, Vec<_>) = objects
+            .iter()
+            .filter(|object| frustum.intersects_object(object))
+            .map(DrawItem::new)
+            .partition(DrawItem::is_transparent);
+
+        // 不透明なObjectは描画順に依存しないため、状態の切り替えが少なくなるように並べる
+        opaques.sort_by_key(|item| {
+            (
+                item.program,
+                item.texture.as_ref().map(Rc::as_ptr),
+                mesh_key(&item.object.mesh),
+            )
+        });
+        sort_back_to_front(&mut transparents, camera);
+
+        let culled = objects.len() - opaques.len() - transparents.len();
+        RenderQueue {
+            opaques,
+            transparents,
+            culled,
+        }
+    }
+}
+
+/// Objectに貼り付けるテクスチャを返す
+/// Materialのテクスチャが設定されていれば、Meshのテクスチャより優先する
+/// テクスチャ座標を持たないMeshの場合は `None` を返す
+fn object_texture(object: &Object) -> Option<Rc<Texture>> {
+    let mesh_texture = object.mesh.texture.as_ref()?;
+    Some(
+        object
+            .material
+            .texture()
+            .unwrap_or_else(|| mesh_texture.data.clone()),
+    )
+}
+
+/// カメラから遠いものが先頭に来るように並び替える
+/// 距離は、View座標系におけるMeshの中心の深度で比較する
+fn sort_back_to_front(items: &mut Vec<DrawItem>, camera: &Camera) {
+    let view_matrix = camera.view_matrix();
+    let depth = |object: &Object| {
+        let center = object.mesh.bounds.map_or(Vector3::zero(), |b| b.center());
+        let m_matrix = object.transform.world_matrix();
+        // View座標系ではカメラは -Z 方向を向いている
+        -(view_matrix * m_matrix * center.extend(1.0)).z
+    };
+
+    let mut with_depth = items
+        .drain(..)
+        .map(|item| (depth(item.object), item))
+        .collect::<Vec<_>>();
+    with_depth.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    items.extend(with_depth.into_iter().map(|(_, item)| item));
+}
+
+/// 同じ頂点バッファを使うMeshで等しくなる値
+/// `Mesh::clone` で生成されたMeshは元のMeshと同じ値になる
+type MeshKey = [usize; 5];
+
+fn mesh_key(mesh: &Mesh) -> MeshKey {
+    [
+        Rc::as_ptr(&mesh.positions_vbo) as usize,
+        Rc::as_ptr(&mesh.colors_vbo) as usize,
+        Rc::as_ptr(&mesh.normals_vbo) as usize,
+        Rc::as_ptr(&mesh.indexes_ibo) as usize,
+        mesh.texture
+            .as_ref()
+            .map_or(0, |t| Rc::as_ptr(&t.coord) as usize),
+    ]
+}
+
+/// 描画中のWebGLの状態を追跡し、変更が不要な場合はそれを数える
+/// 変更が必要な場合は `stats` に数える
+pub(crate) struct DrawState {
+    program: Option<ProgramKey>,
+    /// このフレームでフレーム共通のuniform変数を送信済みのProgramと、
+    /// それぞれに最後に送信したMaterial
+    programs: Vec<(ProgramKey, Option<*const Material>)>,
+    mesh: Option<MeshKey>,
+    texture: Option<*const Texture>,
+    pub saved: SavedStateChanges,
+    pub stats: RenderStats,
+}
+
+impl DrawState {
+    pub fn new(stats: RenderStats) -> Self {
+        DrawState {
+            program: None,
+            programs: Vec::new(),
+            mesh: None,
+            texture: None,
+            saved: SavedStateChanges::default(),
+            stats,
+        }
+    }
+
+    /// `program` に切り替える必要があれば `true` を返す
+    pub fn use_program(&mut self, program: ProgramKey) -> bool {
+        if self.program == Some(program) {
+            self.saved.program_switches += 1;
+            return false;
+        }
+        self.program = Some(program);
+        self.stats.program_switches += 1;
+        // attributeの位置はProgramごとに異なるため、Meshを設定し直す
+        self.mesh = None;
+        true
+    }
+
+    /// 現在のProgramにフレーム共通のuniform変数を送信する必要があれば `true` を返す
+    pub fn use_frame_uniforms(&mut self) -> bool {
+        let program = self.program.unwrap();
+        if self.programs.iter().any(|(p, _)| *p == program) {
+            self.saved.uniform_uploads += 1;
+            return false;
+        }
+        self.programs.push((program, None));
+        true
+    }
+
+    /// 現在のProgramに `material` のuniform変数を送信する必要があれば `true` を返す
+    pub fn use_material(&mut self, material: &Rc<Material>) -> bool {
+        let program = self.program.unwrap();
+        let ptr = Some(Rc::as_ptr(material));
+        let entry = self
+            .programs
+            .iter_mut()
+            .find(|(p, _)| *p == program)
+            .unwrap();
+        if entry.1 == ptr {
+            self.saved.uniform_uploads += 1;
+            return false;
+        }
+        entry.1 = ptr;
+        true
+    }
+
+    /// `mesh` の頂点バッファを設定する必要があれば `true` を返す
+    pub fn use_mesh(&mut self, mesh: &Mesh) -> bool {
+        let key = Some(mesh_key(mesh));
+        if self.mesh == key {
+            self.saved.mesh_binds += 1;
+            return false;
+        }
+        self.mesh = key;
+        true
+    }
+
+    /// `texture` をバインドする必要があれば `true` を返す
+    pub fn use_texture(&mut self, texture: &Rc<Texture>) -> bool {
+        let ptr = Some(Rc::as_ptr(texture));
+        if self.texture == ptr {
+            self.saved.texture_binds += 1;
+            return false;
+        }
+        self.texture = ptr;
+        self.stats.texture_binds += 1;
+        true
+    }
+}
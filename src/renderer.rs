@@ -1,152 +1,677 @@
 use crate::{
     camera::Camera,
-    light::Light,
+    light::{Attenuation, Light, MAX_LIGHTS},
+    material::ShadingModel,
     object::Object,
-    programs::{BasicParams, BasicProgram, TextureProgram},
-    scene::Scene,
+    programs::{BasicParams, BasicProgram, PickingProgram, TextureProgram},
+    render_queue::{DrawItem, DrawState, RenderQueue, SavedStateChanges},
+    scene::{ObjectId, Scene},
+    shadow::{ShadowInfo, ShadowMap, ShadowSettings},
+    stats::{self, RenderStats},
 };
-use cgmath::prelude::*;
+use cgmath::{prelude::*, vec4, Matrix4, Vector2, Vector3, Vector4};
 use napier_webgl::{
-    context::{self, BlendFactor, DepthFunc},
+    context::{self, BlendFactor, Context, ContextId, DepthFunc},
+    error::Error,
+    framebuffer::GlFramebuffer,
     texture::GlTextureUnit,
 };
-use wasm_bindgen::JsValue;
+use std::time::Duration;
 use web_sys::WebGlRenderingContext as GL;
 
 pub struct Renderer {
-    basic_program: BasicProgram,
-    texture_program: TextureProgram,
+    basic_programs: ShadingPrograms<BasicProgram>,
+    texture_programs: ShadingPrograms<TextureProgram>,
+    // ピッキング用のProgramとフレームバッファ
+    // 最初に `pick` が呼ばれたときに生成する
+    picking_program: Option<PickingProgram>,
+    picking_framebuffer: Option<GlFramebuffer>,
+    shadow_settings: ShadowSettings,
+    // 影を描画する設定で、最初に `render` が呼ばれたときに生成する
+    shadow_map: Option<ShadowMap>,
+    saved_state_changes: SavedStateChanges,
+    stats: RenderStats,
+    // 前回の `render` の終了時点での、VBOやIBOへの転送回数
+    buffer_uploads: usize,
+    // 描画先のContext
+    context: ContextId,
+    // 各Programやフレームバッファを生成したContextの世代
+    generation: u64,
 }
 
 impl Renderer {
     /// このライブラリを利用するときのエントリーポイント
-    pub fn new() -> Result<Self, JsValue> {
-        context::with(|ctx| {
-            ctx.enable_culling();
-            ctx.enable_depth_test(DepthFunc::LEqual);
-            // 透過処理のブレンディングを有効化
-            ctx.enable_separate_blending(
-                BlendFactor::SrcAlpha,         // src_rgb
-                BlendFactor::OneMinusSrcAlpha, // dst_rgb
-                BlendFactor::One,              // src_alpha
-                BlendFactor::One,              // dst_alpha
-            );
-        });
+    ///
+    /// 操作対象のContext（`napier::init` で初期化したContextなど）に描画するRendererを生成する。
+    /// 複数のcanvasに描画する場合は、canvasごとにContextを生成し、
+    /// それぞれを操作対象にしてからRendererを生成する。
+    ///
+    /// 操作対象のContextが無い場合は `Error::ContextUnavailable` を返す。
+    pub fn new() -> Result<Self, Error> {
+        let (buffer_uploads, context, generation) = context::try_with(|ctx| {
+            init_gl_state(ctx);
+            (ctx.buffer_uploads(), ctx.id(), ctx.generation())
+        })?;
 
         Ok(Renderer {
-            basic_program: BasicProgram::gouraud()?,
-            texture_program: TextureProgram::phong()?,
+            basic_programs: ShadingPrograms::new(BasicProgram::phong, BasicProgram::gouraud),
+            texture_programs: ShadingPrograms::new(TextureProgram::phong, TextureProgram::gouraud),
+            picking_program: None,
+            picking_framebuffer: None,
+            shadow_settings: ShadowSettings::new(),
+            shadow_map: None,
+            saved_state_changes: SavedStateChanges::default(),
+            stats: RenderStats::default(),
+            buffer_uploads,
+            context,
+            generation,
         })
     }
 
-    pub fn render(&mut self, scene: &Scene, camera: &Camera) {
+    /// 描画先のContext
+    pub fn context_id(&self) -> ContextId {
+        self.context
+    }
+
+    /// 失われたContextが復元されていれば、Rendererが保持するProgramやフレームバッファを作り直す。
+    /// Contextが失われている間は `false` を返す。
+    /// Contextが既に破棄されている場合は `Error::ContextUnavailable` を返す。
+    ///
+    /// MeshやTextureは転送したデータをCPU側にも保持しており、
+    /// 次に描画に使われるときにそれぞれ作り直される。
+    fn restore(&mut self) -> Result<bool, Error> {
+        let (is_lost, generation) =
+            context::try_with_id(self.context, |ctx| (ctx.is_lost(), ctx.generation()))?;
+        // 以降の描画やGPUリソースの生成は、このRendererのContextに対して行う
+        context::make_current(self.context);
+        if is_lost {
+            return Ok(false);
+        }
+        if generation == self.generation {
+            return Ok(true);
+        }
+
+        context::with(init_gl_state);
+        self.basic_programs = ShadingPrograms::new(BasicProgram::phong, BasicProgram::gouraud);
+        self.texture_programs =
+            ShadingPrograms::new(TextureProgram::phong, TextureProgram::gouraud);
+        self.picking_program = None;
+        self.picking_framebuffer = None;
+        self.shadow_map = None;
+        self.generation = generation;
+        Ok(true)
+    }
+
+    pub fn shadow_settings(&self) -> ShadowSettings {
+        self.shadow_settings
+    }
+
+    /// 直前の `render` で省略できた状態変更の回数
+    pub fn saved_state_changes(&self) -> SavedStateChanges {
+        self.saved_state_changes
+    }
+
+    /// 直前の `render` で行った描画の統計
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// 影の描画に関する設定を変更する。
+    /// 解像度が変わった場合は、次の `render` でシャドウマップを作り直す。
+    /// 影を描画しない設定にした場合は、シャドウマップを破棄する。
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) -> Result<(), Error> {
+        let keep = settings.enabled
+            && self
+                .shadow_map
+                .as_ref()
+                .is_some_and(|map| map.resolution() == settings.resolution);
+        if !keep {
+            self.shadow_map = None;
+        }
+        self.shadow_settings = settings;
+        Ok(())
+    }
+
+    /// シャドウマップを返す。まだ生成していなければ生成する
+    fn shadow_map(&mut self) -> Result<&mut ShadowMap, Error> {
+        let shadow_map = match self.shadow_map.take() {
+            Some(shadow_map) => shadow_map,
+            None => ShadowMap::new(self.shadow_settings.resolution)?,
+        };
+        Ok(self.shadow_map.insert(shadow_map))
+    }
+
+    /// `scene` を `camera` から見た様子をcanvasに描画する。
+    /// 各Objectの描画に必要なProgramは、最初に必要になったときに生成される。
+    /// カメラの視錐台の外にあるObjectは描画されない。
+    ///
+    /// Contextが失われている間は何もしない。
+    /// Contextが復元された後の最初の呼び出しで、必要なGPUリソースが作り直される。
+    ///
+    /// 呼び出し後は、このRendererのContextが操作対象のContextになる。
+    pub fn render(&mut self, scene: &Scene, camera: &Camera) -> Result<(), Error> {
+        if !self.restore()? {
+            return Ok(());
+        }
+
+        let start_ms = stats::now_ms();
+        let mut stats = RenderStats::default();
+
+        // 光源から見た深度をシャドウマップに書き込む
+        let shadow = if self.shadow_settings.enabled {
+            let settings = self.shadow_settings;
+            self.shadow_map()?
+                .render(scene, camera, &settings, &mut stats)?
+        } else {
+            None
+        };
+
         context::with(|ctx| {
+            enable_blending(ctx);
+
             // 背景色と深度の設定
             ctx.clear_color_and_depth(scene.background.to_f32(), 1.0);
         });
 
-        for object in scene.objects() {
-            if object.mesh.texture.is_some() {
-                render_texture_object(&mut self.texture_program, scene, camera, object);
-            } else {
-                render_basic_object(&mut self.basic_program, scene, camera, object);
+        // シャドウマップは `SHADOW_MAP_UNIT` に固定で割り当てる
+        if let Some(shadow_map) = self.shadow_map.as_ref() {
+            SHADOW_MAP_UNIT.activate();
+            shadow_map.texture().bind()?;
+            stats.texture_binds += 1;
+        }
+
+        // 不透明なObjectを先に描画し、半透明なObjectは奥にあるものから順に描画する
+        // カメラに写らないObjectはキューに積まれない
+        // 写っていないObjectも影は落とすため、シャドウマップには全て描画する
+        let queue = RenderQueue::new(scene, camera);
+        stats.culled_objects = queue.culled;
+        let mut state = DrawState::new(stats);
+
+        let shadow = shadow.as_ref();
+        for item in queue.opaques.iter() {
+            self.draw(scene, camera, item, shadow, &mut state)?;
+        }
+
+        // 半透明なObjectは、後ろにあるObjectを隠さないよう深度を書き込まない
+        context::with(|ctx| ctx.disable_depth_write());
+        let result = queue
+            .transparents
+            .iter()
+            .try_for_each(|item| self.draw(scene, camera, item, shadow, &mut state));
+        context::with(|ctx| ctx.enable_depth_write());
+
+        self.saved_state_changes = state.saved;
+        self.stats = state.stats;
+
+        let buffer_uploads = context::with(|ctx| ctx.buffer_uploads());
+        self.stats
+            .set_buffer_uploads(buffer_uploads, self.buffer_uploads);
+        self.buffer_uploads = buffer_uploads;
+        self.stats.cpu_time =
+            Duration::from_secs_f64((stats::now_ms() - start_ms).max(0.0) / 1000.0);
+        result
+    }
+
+    /// 1つのObjectを描画する。
+    /// `state` と比べて変更が必要な状態のみを設定する。
+    fn draw(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        item: &DrawItem,
+        shadow: Option<&ShadowInfo>,
+        state: &mut DrawState,
+    ) -> Result<(), Error> {
+        let object = item.object;
+        let shading = item.program.shading();
+
+        if let Some(texture) = item.texture.as_ref() {
+            let program = self.texture_programs.get_or_build(shading)?;
+            if state.use_program(item.program) {
+                program.switch();
+            }
+
+            let params = program.params_mut();
+            if state.use_frame_uniforms() {
+                set_frame_uniforms(params.as_mut(), scene, camera, shadow)?;
+                params.texture.set_value(GlTextureUnit::Unit0)?;
+            }
+            set_object_uniforms(params.as_mut(), camera, object, shadow, state)?;
+
+            if state.use_mesh(&object.mesh) {
+                set_basic_attrs(params.as_ref(), object)?;
+                // texCoord attributeの設定
+                let mesh_texture = object.mesh.texture.as_ref().unwrap();
+                params.tex_coord.attach_vbo(&mesh_texture.coord)?;
+            }
+
+            if state.use_texture(texture) {
+                if texture.gl.context() != self.context {
+                    return Err(Error::ContextMismatch);
+                }
+                GlTextureUnit::Unit0.activate();
+                texture.gl.bind()?;
+            }
+        } else {
+            let program = self.basic_programs.get_or_build(shading)?;
+            if state.use_program(item.program) {
+                program.switch();
+            }
+
+            let params = program.params_mut();
+            if state.use_frame_uniforms() {
+                set_frame_uniforms(params, scene, camera, shadow)?;
+            }
+            set_object_uniforms(params, camera, object, shadow, state)?;
+
+            if state.use_mesh(&object.mesh) {
+                set_basic_attrs(params, object)?;
             }
         }
+
+        context::with(|ctx| {
+            ctx.draw_elements_with_i32(GL::TRIANGLES, object.mesh.index_len, GL::UNSIGNED_SHORT, 0);
+        });
+        state.stats.count_draw(object.mesh.index_len);
+        Ok(())
     }
-}
 
-fn render_basic_object(
-    program: &mut BasicProgram,
-    scene: &Scene,
-    camera: &Camera,
-    object: &Object,
-) {
-    program.switch();
+    /// canvas上の点 (`x`, `y`) に写っているObjectのIDを返す。
+    /// 何も写っていない場合は `None` を返す。
+    ///
+    /// Objectごとに異なる色でオフスクリーンに描画し、その点の色を読み出して判定する。
+    /// 座標はcanvasの左上を原点とした、描画領域のピクセル単位で指定する。
+    /// canvasの表示サイズと描画領域のサイズが同じであれば、
+    /// `napier_window::event::MouseEvent::x()` などの値をそのまま渡せる。
+    pub fn pick(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        x: f64,
+        y: f64,
+    ) -> Result<Option<ObjectId>, Error> {
+        if !self.restore()? {
+            return Ok(None);
+        }
 
-    set_basic_uniforms(program.params_mut(), scene, camera, object);
+        let (width, height) =
+            context::with(|ctx| (ctx.drawing_buffer_width(), ctx.drawing_buffer_height()));
 
-    set_basic_attrs(program.params(), object);
+        // WebGLの座標系は左下が原点
+        let (x, y) = (x as i32, height - 1 - y as i32);
+        if x < 0 || width <= x || y < 0 || height <= y {
+            return Ok(None);
+        }
 
-    context::with(|ctx| {
-        ctx.draw_elements_with_i32(GL::TRIANGLES, object.mesh.index_len, GL::UNSIGNED_SHORT, 0);
-    })
+        // 描画領域のサイズが変わっていたらフレームバッファを作り直す
+        let is_same_size = |fb: &GlFramebuffer| fb.width() == width && fb.height() == height;
+        if !self.picking_framebuffer.as_ref().is_some_and(is_same_size) {
+            self.picking_framebuffer = Some(GlFramebuffer::new(width, height)?);
+        }
+        if self.picking_program.is_none() {
+            self.picking_program = Some(PickingProgram::new()?);
+        }
+        let framebuffer = self.picking_framebuffer.as_ref().unwrap();
+        let program = self.picking_program.as_mut().unwrap();
+
+        framebuffer.bind();
+        context::with(|ctx| {
+            // 色を混ぜないようにブレンディングを無効化する
+            ctx.disable_blending();
+            ctx.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+        });
+
+        program.switch();
+        let vp_matrix = camera.matrix();
+        let result = scene
+            .objects()
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, object)| {
+                let params = program.params_mut();
+                params
+                    .mvp_matrix
+                    .set_value(vp_matrix * object.transform.world_matrix())?;
+                // 0 (黒) は何も写っていないことを表すため、1から割り当てる
+                params.id_color.set_value(index_to_color(idx + 1))?;
+
+                program
+                    .params()
+                    .position
+                    .attach_vbo(&object.mesh.positions_vbo)?;
+                object.mesh.indexes_ibo.bind()?;
+
+                context::with(|ctx| {
+                    ctx.draw_elements_with_i32(
+                        GL::TRIANGLES,
+                        object.mesh.index_len,
+                        GL::UNSIGNED_SHORT,
+                        0,
+                    );
+                });
+                Ok(())
+            });
+
+        let pixel = result.and_then(|_| framebuffer.read_pixel(x, y));
+        framebuffer.unbind();
+        context::with(enable_blending);
+
+        let idx = color_to_index(pixel?);
+        Ok(idx
+            .checked_sub(1)
+            .and_then(|idx| scene.iter().nth(idx))
+            .map(|(id, _)| id))
+    }
 }
 
-fn render_texture_object(
-    program: &mut TextureProgram,
-    scene: &Scene,
-    camera: &Camera,
-    object: &Object,
-) {
-    program.switch();
+/// 陰影の付け方ごとのProgram
+/// 各Programは最初に必要になったときに生成し、以降は使い回す
+struct ShadingPrograms<P> {
+    phong: Option<P>,
+    gouraud: Option<P>,
+    build_phong: fn() -> Result<P, Error>,
+    build_gouraud: fn() -> Result<P, Error>,
+}
+
+impl<P> ShadingPrograms<P> {
+    fn new(build_phong: fn() -> Result<P, Error>, build_gouraud: fn() -> Result<P, Error>) -> Self {
+        ShadingPrograms {
+            phong: None,
+            gouraud: None,
+            build_phong,
+            build_gouraud,
+        }
+    }
 
-    set_basic_uniforms(program.params_mut().as_mut(), scene, camera, object);
+    fn get_or_build(&mut self, shading: ShadingModel) -> Result<&mut P, Error> {
+        let (program, build) = match shading {
+            ShadingModel::Phong => (&mut self.phong, self.build_phong),
+            // 光の計算を行わないため、負荷の低いグーローシェーディング版を使う
+            ShadingModel::Gouraud | ShadingModel::Unlit => (&mut self.gouraud, self.build_gouraud),
+        };
+        if program.is_none() {
+            *program = Some(build()?);
+        }
+        Ok(program.as_mut().unwrap())
+    }
+}
 
-    set_basic_attrs(program.params().as_ref(), object);
+/// シャドウマップを割り当てるテクスチャユニット
+/// Objectのテクスチャは `GlTextureUnit::Unit0` を使う
+const SHADOW_MAP_UNIT: GlTextureUnit = GlTextureUnit::Unit1;
 
-    // テクスチャの関連の設定
-    let texture = object.mesh.texture.as_ref().unwrap();
+/// 透過処理のブレンディングを有効化
+/// `Renderer` が前提とするWebGLの状態を設定する
+fn init_gl_state(ctx: &mut Context) {
+    ctx.enable_culling();
+    ctx.enable_depth_test(DepthFunc::LEqual);
+    enable_blending(ctx);
+}
 
-    // texCoord attributeの設定
-    program.params().tex_coord.attach_vbo(&texture.coord);
+fn enable_blending(ctx: &mut Context) {
+    ctx.enable_separate_blending(
+        BlendFactor::SrcAlpha,         // src_rgb
+        BlendFactor::OneMinusSrcAlpha, // dst_rgb
+        BlendFactor::One,              // src_alpha
+        BlendFactor::One,              // dst_alpha
+    );
+}
 
-    // テクスチャユニットの設定
-    texture.data.gl.bind();
-    let unit = GlTextureUnit::Unit0;
-    unit.activate();
-    program.params_mut().texture.set_value(unit);
+/// インデックスを、下位のバイトから順にRGBに割り当てた色に変換する
+fn index_to_color(idx: usize) -> Vector4<f32> {
+    let byte = |shift: usize| ((idx >> shift) & 0xff) as f32 / 255.0;
+    vec4(byte(0), byte(8), byte(16), 1.0)
+}
 
-    context::with(|ctx| {
-        ctx.draw_elements_with_i32(GL::TRIANGLES, object.mesh.index_len, GL::UNSIGNED_SHORT, 0);
-    })
+fn color_to_index(pixel: [u8; 4]) -> usize {
+    pixel[0] as usize | (pixel[1] as usize) << 8 | (pixel[2] as usize) << 16
 }
 
-fn set_basic_uniforms(params: &mut BasicParams, scene: &Scene, camera: &Camera, object: &Object) {
+/// フレーム内の全てのObjectで共通のuniform変数を設定する
+fn set_frame_uniforms(
+    params: &mut BasicParams,
+    scene: &Scene,
+    camera: &Camera,
+    shadow: Option<&ShadowInfo>,
+) -> Result<(), Error> {
     // ambient_color の設定
     params
         .ambient_color
-        .set_value(scene.ambient_color.to_f32_vec4());
+        .set_value(scene.ambient_color.to_f32_vec4())?;
 
     // eye_directionの設定
     let eye_direction = camera.look_at - camera.pos;
-    params.eye_direction.set_value(eye_direction);
+    params.eye_direction.set_value(eye_direction)?;
 
     // lightの設定
-    match scene.light {
-        Some(Light::Directional(ref light)) => {
-            params.light_type.set_value(1);
-            params.light_val.set_value(light.dir);
+    // 配列の長さは常に `MAX_LIGHTS` に揃え、使われない要素は0で埋める
+    let mut light_types = vec![0; MAX_LIGHTS];
+    let mut light_vals = vec![Vector3::zero(); MAX_LIGHTS];
+    let mut light_colors = vec![Vector3::zero(); MAX_LIGHTS];
+    let mut light_attenuations = vec![Attenuation::none().to_vec3(); MAX_LIGHTS];
+    let mut light_directions = vec![Vector3::zero(); MAX_LIGHTS];
+    let mut light_cones = vec![Vector2::zero(); MAX_LIGHTS];
+    let lights = scene.lights.iter().take(MAX_LIGHTS);
+    for (i, light) in lights.enumerate() {
+        light_colors[i] = light.radiance();
+        match light {
+            Light::Directional(light) => {
+                light_types[i] = 1;
+                light_vals[i] = light.dir;
+            }
+            Light::Point(light) => {
+                light_types[i] = 2;
+                light_vals[i] = light.pos;
+                light_attenuations[i] = light.attenuation.to_vec3();
+            }
+            Light::Spot(light) => {
+                light_types[i] = 3;
+                light_vals[i] = light.pos;
+                light_attenuations[i] = light.attenuation.to_vec3();
+                light_directions[i] = light.dir;
+                light_cones[i] = light.cone_cos();
+            }
         }
-        Some(Light::Point(ref light)) => {
-            params.light_type.set_value(2);
-            params.light_val.set_value(light.pos);
+    }
+    let light_count = scene.lights.len().min(MAX_LIGHTS);
+    params.light_count.set_value(light_count as i32)?;
+    params.light_types.set_value(light_types)?;
+    params.light_vals.set_value(light_vals)?;
+    params.light_colors.set_value(light_colors)?;
+    params.light_attenuations.set_value(light_attenuations)?;
+    params.light_directions.set_value(light_directions)?;
+    params.light_cones.set_value(light_cones)?;
+
+    // 影の設定
+    match shadow {
+        Some(shadow) => {
+            params.shadow_matrix.set_value(shadow.matrix)?;
+            params.shadow_bias.set_value(shadow.bias)?;
         }
         None => {
-            params.light_type.set_value(0);
+            params.shadow_matrix.set_value(Matrix4::identity())?;
+            params.shadow_bias.set_value(0.0)?;
         }
     }
+    params.shadow_map.set_value(SHADOW_MAP_UNIT)
+}
+
+/// Objectごとに異なるuniform変数を設定する
+/// Materialは、直前に設定したものと異なる場合のみ設定する
+fn set_object_uniforms(
+    params: &mut BasicParams,
+    camera: &Camera,
+    object: &Object,
+    shadow: Option<&ShadowInfo>,
+    state: &mut DrawState,
+) -> Result<(), Error> {
+    // Materialの設定
+    let material = &object.material;
+    if state.use_material(material) {
+        params.base_color.set_value(material.color_vec4())?;
+        params.shininess.set_value(material.shininess.get())?;
+        params
+            .specular_strength
+            .set_value(material.specular_strength.get())?;
+        params.emissive.set_value(material.emissive_vec3())?;
+        params
+            .unlit
+            .set_value(material.shading.get() == ShadingModel::Unlit)?;
+    }
+
+    // 影を受けるかどうかの設定
+    let shadow_light = match shadow {
+        Some(shadow) if object.receives_shadow.get() => shadow.light_index as i32,
+        _ => -1,
+    };
+    params.shadow_light.set_value(shadow_light)?;
 
     // カメラ, Transform周りの設定
     let vp_matrix = camera.matrix();
-    let m_matrix = object.transform.matrix();
-    params.m_matrix.set_value(m_matrix);
-    params.mvp_matrix.set_value(vp_matrix * m_matrix);
-    params.inv_m_matrix.set_value(m_matrix.invert().unwrap());
+    let m_matrix = object.transform.world_matrix();
+    params.m_matrix.set_value(m_matrix)?;
+    params.mvp_matrix.set_value(vp_matrix * m_matrix)?;
+    params
+        .normal_matrix
+        .set_value(object.transform.normal_matrix())
 }
 
-fn set_basic_attrs(params: &BasicParams, object: &Object) {
+fn set_basic_attrs(params: &BasicParams, object: &Object) -> Result<(), Error> {
     // 各attribute変数の設定
     let mesh = &object.mesh;
 
     // "position" attributeの設定
-    params.position.attach_vbo(&mesh.positions_vbo);
+    params.position.attach_vbo(&mesh.positions_vbo)?;
 
     // "normal" attributeの設定
-    params.normal.attach_vbo(&mesh.normals_vbo);
+    params.normal.attach_vbo(&mesh.normals_vbo)?;
 
     // "color" attributeの設定
-    params.color.attach_vbo(&mesh.colors_vbo);
+    params.color.attach_vbo(&mesh.colors_vbo)?;
 
     // Index Bufferの設定
-    mesh.indexes_ibo.bind();
+    mesh.indexes_ibo.bind()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meshes, test_util, Color, Light};
+    use cgmath::vec3;
+    use wasm_bindgen_test::*;
+
+    /// GPUがフレームバッファへ書き込むときと同様に、色を8bitに量子化する
+    fn quantize(color: Vector4<f32>) -> [u8; 4] {
+        let to_u8 = |c: f32| (c * 255.0).round() as u8;
+        [
+            to_u8(color.x),
+            to_u8(color.y),
+            to_u8(color.z),
+            to_u8(color.w),
+        ]
+    }
+
+    #[wasm_bindgen_test]
+    fn test_index_color_round_trip() {
+        // RGBの24bitで表せる最大のインデックス
+        let max = 0xff_ffff;
+        for idx in [0, 1, 255, 256, 257, 0xffff, 0x1_0000, max] {
+            assert_eq!(color_to_index(quantize(index_to_color(idx))), idx);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_render_after_destroy() {
+        let id = crate::create_context(&test_util::canvas()).unwrap();
+        crate::use_context(id);
+        let mut renderer = Renderer::new().unwrap();
+        let camera = Camera::new();
+        let mut scene = Scene::new();
+        scene.add(&Object::new(
+            meshes::rect(1.0, 1.0, Color::white()).unwrap(),
+        ));
+        assert!(renderer.render(&scene, &camera).is_ok());
+
+        context::destroy(id);
+        assert_eq!(
+            renderer.render(&scene, &camera),
+            Err(Error::ContextUnavailable(None))
+        );
+        assert_eq!(
+            renderer.pick(&scene, &camera, 0.0, 0.0),
+            Err(Error::ContextUnavailable(None))
+        );
+        // 操作対象のContextも無くなっている
+        assert!(matches!(
+            Renderer::new(),
+            Err(Error::ContextUnavailable(None))
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_render_mesh_of_other_context() {
+        let other = crate::create_context(&test_util::canvas()).unwrap();
+        crate::use_context(other);
+        let object = Object::new(meshes::rect(1.0, 1.0, Color::white()).unwrap());
+
+        let id = crate::create_context(&test_util::canvas()).unwrap();
+        crate::use_context(id);
+        let mut renderer = Renderer::new().unwrap();
+        let camera = Camera::new();
+        let mut scene = Scene::new();
+        scene.add(&object);
+        assert_eq!(
+            renderer.render(&scene, &camera),
+            Err(Error::ContextMismatch)
+        );
+        assert_eq!(
+            renderer.pick(&scene, &camera, 0.0, 0.0),
+            Err(Error::ContextMismatch)
+        );
+
+        drop(scene);
+        drop(object);
+        context::destroy(id);
+        context::destroy(other);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_stats_per_frame() {
+        let id = crate::create_context(&test_util::canvas()).unwrap();
+        crate::use_context(id);
+        let mut renderer = Renderer::new().unwrap();
+        let mut camera = Camera::new();
+        camera.pos = vec3(0.0, 0.0, 10.0);
+        camera.up = vec3(0.0, 1.0, 0.0);
+
+        let mut scene = Scene::new();
+        scene.lights.push(Light::directional(0.0, 0.0, -1.0));
+        let opaque = Object::new(meshes::rect(1.0, 1.0, Color::white()).unwrap());
+        let transparent = Object::new(meshes::rect(1.0, 1.0, Color::rgba(255, 0, 0, 0.5)).unwrap());
+        scene.add(&opaque);
+        scene.add(&transparent);
+
+        renderer.render(&scene, &camera).unwrap();
+        let first = renderer.stats();
+        // シャドウマップに2回、不透明と半透明のObjectを1回ずつ描画する
+        assert_eq!(first.draw_calls, 4);
+        assert_eq!(first.triangles, 8);
+        // シャドウマップ用と、両方のObjectで共通のProgram
+        assert_eq!(first.program_switches, 2);
+        assert_eq!(first.culled_objects, 0);
+        // 2つのMeshそれぞれで、3つのVBOと1つのIBOに転送している
+        assert_eq!(first.buffer_uploads, 8);
+
+        // 前のフレームの値は引き継がない
+        renderer.render(&scene, &camera).unwrap();
+        let second = renderer.stats();
+        assert_eq!(second.draw_calls, first.draw_calls);
+        assert_eq!(second.triangles, first.triangles);
+        assert_eq!(second.program_switches, first.program_switches);
+        assert_eq!(second.texture_binds, first.texture_binds);
+        assert_eq!(second.buffer_uploads, 0);
+
+        drop(renderer);
+        context::destroy(id);
+    }
 }
@@ -1,19 +1,33 @@
-use crate::{Color, Light, Object};
+use crate::{picking, ray::Ray, Color, Hit, Light, Object};
+
+/// `Scene` に追加された `Object` を識別するためのハンドル
+///
+/// 同じ `Scene` の中では一度使われたIDが再利用されることはない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId(u64);
 
 pub struct Scene {
     objects: Vec<Object>,
+    // `objects` と同じ順序で並んだ各ObjectのID
+    ids: Vec<ObjectId>,
+    next_id: u64,
     pub background: Color,
+    /// 環境光の色。アルファ値は使われない
     pub ambient_color: Color,
-    pub light: Option<Light>,
+    /// Sceneを照らす光源のリスト
+    /// 先頭から `light::MAX_LIGHTS` 個までが描画に使用される。
+    pub lights: Vec<Light>,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Scene {
             objects: Vec::new(),
+            ids: Vec::new(),
+            next_id: 0,
             background: Color::black(),
             ambient_color: Color::rgba(25, 25, 25, 0.1),
-            light: None,
+            lights: Vec::new(),
         }
     }
 
@@ -21,11 +35,155 @@ impl Scene {
         &self.objects[..]
     }
 
-    pub fn add(&mut self, object: &Object) {
+    /// 追加された順に `ObjectId` と `Object` の組を返す
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &Object)> {
+        self.ids.iter().copied().zip(self.objects.iter())
+    }
+
+    /// `Object` をSceneに追加し、そのIDを返す。
+    /// すでに同じObjectが追加されている場合は、何もせずに既存のIDを返す。
+    pub fn add(&mut self, object: &Object) -> ObjectId {
+        if let Some(id) = self.find(object) {
+            return id;
+        }
+
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+
         self.objects.push(object.shared_clone());
+        self.ids.push(id);
+
+        id
+    }
+
+    /// `object` と同じObjectがSceneに含まれていれば、そのIDを返す
+    pub fn find(&self, object: &Object) -> Option<ObjectId> {
+        self.objects
+            .iter()
+            .position(|o| o.is_same(object))
+            .map(|idx| self.ids[idx])
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&Object> {
+        self.position(id).map(|idx| &self.objects[idx])
+    }
+
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// 指定されたIDのObjectをSceneから取り除き、それを返す。
+    /// 残りのObjectの描画順序は保たれる。
+    pub fn remove(&mut self, id: ObjectId) -> Option<Object> {
+        let idx = self.position(id)?;
+        self.ids.remove(idx);
+        Some(self.objects.remove(idx))
+    }
+
+    /// `ray` と交差するObjectのうち、最もRayの始点に近いものを返す。
+    /// `Camera::screen_point_to_ray` と組み合わせることで、
+    /// 画面上の点に写っているObjectを求めることができる。
+    pub fn pick(&self, ray: &Ray) -> Option<Hit> {
+        picking::pick(self, ray)
+    }
+
+    /// 全てのObjectをSceneから取り除く
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        self.ids.clear();
+    }
+
+    fn position(&self, id: ObjectId) -> Option<usize> {
+        // IDは昇順に発行され、削除しても順序は崩れないため二分探索できる
+        self.ids.binary_search(&id).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{meshes, test_util};
+    use napier_webgl::context::{self, ContextId};
+    use wasm_bindgen_test::*;
+
+    // MeshのVBOを生成するため、Contextを用意する
+    fn init() -> ContextId {
+        let id = crate::create_context(&test_util::canvas()).unwrap();
+        crate::use_context(id);
+        id
+    }
+
+    fn object() -> Object {
+        Object::new(meshes::rect(1.0, 1.0, Color::white()).unwrap())
+    }
+
+    #[wasm_bindgen_test]
+    fn test_add_same_object() {
+        let context = init();
+        let mut scene = Scene::new();
+        let a = object();
+        let b = object();
+
+        let id = scene.add(&a);
+        assert_eq!(scene.add(&a), id);
+        assert_eq!(scene.add(&a.shared_clone()), id);
+        assert_eq!(scene.find(&a.shared_clone()), Some(id));
+
+        let other = scene.add(&b);
+        assert_ne!(other, id);
+        assert_eq!(scene.objects().len(), 2);
+        assert_eq!(
+            scene.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![id, other]
+        );
+
+        drop(scene);
+        context::destroy(context);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_remove() {
+        let context = init();
+        let mut scene = Scene::new();
+        let a = object();
+        let b = object();
+        let id_a = scene.add(&a);
+        let id_b = scene.add(&b);
+
+        let removed = scene.remove(id_a).unwrap();
+        assert!(removed.is_same(&a));
+        assert!(!scene.contains(id_a));
+        assert!(scene.get(id_a).is_none());
+        assert!(scene.find(&a).is_none());
+        assert!(scene.remove(id_a).is_none());
+
+        assert!(scene.contains(id_b));
+        assert!(scene.get(id_b).unwrap().is_same(&b));
+
+        drop(scene);
+        context::destroy(context);
     }
 
-    pub fn remove(&mut self, _object: &Object) {
-        unimplemented!();
+    #[wasm_bindgen_test]
+    fn test_ids_not_reused_after_clear() {
+        let context = init();
+        let mut scene = Scene::new();
+        let a = object();
+        let old_a = scene.add(&a);
+        let old_b = scene.add(&object());
+
+        scene.clear();
+        assert!(scene.objects().is_empty());
+        assert!(!scene.contains(old_a));
+
+        // 同じObjectを追加し直しても、以前とは異なるIDになる
+        let new_a = scene.add(&a);
+        assert_ne!(new_a, old_a);
+        assert_ne!(new_a, old_b);
+        assert!(!scene.contains(old_a));
+        assert!(scene.contains(new_a));
+
+        drop(scene);
+        context::destroy(context);
     }
 }
@@ -0,0 +1,238 @@
+use crate::{
+    camera::{Camera, Projection},
+    light::{Light, MAX_LIGHTS},
+    programs::ShadowProgram,
+    scene::Scene,
+    stats::RenderStats,
+};
+use cgmath::{prelude::*, vec3, Matrix4, Rad, Vector3};
+use napier_webgl::{context, error::Error, framebuffer::GlFramebuffer, texture::GlTexture};
+use std::f32::consts::PI;
+use web_sys::WebGlRenderingContext as GL;
+
+/// 影の描画に関する設定
+///
+/// 影を落とすのは、`Scene::lights` のうち最初に見つかった平行光源またはスポットライトのみ。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    /// 影を描画するかどうか
+    /// Default : true
+    pub enabled: bool,
+
+    /// シャドウマップの一辺のピクセル数
+    /// Default : 1024
+    pub resolution: i32,
+
+    /// 自身の影が縞模様に写り込む（シャドウアクネ）のを防ぐため、深度の比較に加えるオフセット
+    /// Default : 0.005
+    pub bias: f32,
+
+    /// 平行光源の影を描画する範囲
+    /// カメラの注視点を中心とした、一辺が `2 * extent` の立方体の範囲に影が落ちる
+    /// Default : 20.0
+    pub extent: f32,
+
+    /// スポットライトの影を描画する、光源からの最大距離
+    /// Default : 100.0
+    pub range: f32,
+}
+
+impl ShadowSettings {
+    pub fn new() -> Self {
+        ShadowSettings {
+            enabled: true,
+            resolution: 1024,
+            bias: 0.005,
+            extent: 20.0,
+            range: 100.0,
+        }
+    }
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings::new()
+    }
+}
+
+/// 各Programでシャドウマップを参照するために必要な情報
+pub(crate) struct ShadowInfo {
+    /// 影を落とす光源の `Scene::lights` におけるインデックス
+    pub light_index: usize,
+    /// World座標を、光源から見たクリップ座標に変換する行列
+    pub matrix: Matrix4<f32>,
+    pub bias: f32,
+}
+
+/// 光源から見た深度を書き込むシャドウマップ
+pub(crate) struct ShadowMap {
+    program: ShadowProgram,
+    framebuffer: GlFramebuffer,
+}
+
+impl ShadowMap {
+    pub fn new(resolution: i32) -> Result<Self, Error> {
+        Ok(ShadowMap {
+            program: ShadowProgram::new()?,
+            framebuffer: GlFramebuffer::new(resolution, resolution)?,
+        })
+    }
+
+    pub fn resolution(&self) -> i32 {
+        self.framebuffer.width()
+    }
+
+    /// 深度が書き込まれたテクスチャ
+    pub fn texture(&self) -> &GlTexture {
+        self.framebuffer.texture()
+    }
+
+    /// 影を落とす光源から見た深度をシャドウマップに書き込む。
+    /// 影を落とす光源が無い場合は何もせずに `None` を返す。
+    /// 行った描画は `stats` に加算する。
+    pub fn render(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        settings: &ShadowSettings,
+        stats: &mut RenderStats,
+    ) -> Result<Option<ShadowInfo>, Error> {
+        let found = scene
+            .lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .enumerate()
+            .find_map(|(idx, light)| Some((idx, light_camera(light, camera, settings)?)));
+        let (light_index, light_camera) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let vp_matrix = light_camera.matrix();
+
+        let resolution = self.resolution();
+        self.framebuffer.bind();
+        context::with(|ctx| {
+            // RGBAに深度を格納するため、ブレンディングを無効化する
+            ctx.disable_blending();
+            ctx.viewport(0, 0, resolution, resolution);
+            // 何も描画されない位置は最も遠い深度とする
+            ctx.clear_color_and_depth((1.0, 1.0, 1.0, 1.0), 1.0);
+        });
+
+        self.program.switch();
+        stats.program_switches += 1;
+        let program = &mut self.program;
+        let result: Result<(), Error> = scene
+            .objects()
+            .iter()
+            .filter(|object| object.casts_shadow.get())
+            .try_for_each(|object| {
+                program
+                    .params_mut()
+                    .mvp_matrix
+                    .set_value(vp_matrix * object.transform.world_matrix())?;
+                program
+                    .params()
+                    .position
+                    .attach_vbo(&object.mesh.positions_vbo)?;
+                object.mesh.indexes_ibo.bind()?;
+
+                context::with(|ctx| {
+                    ctx.draw_elements_with_i32(
+                        GL::TRIANGLES,
+                        object.mesh.index_len,
+                        GL::UNSIGNED_SHORT,
+                        0,
+                    );
+                });
+                stats.count_draw(object.mesh.index_len);
+                Ok(())
+            });
+
+        // 描画に失敗した場合も、描画先と描画領域はcanvasに戻す
+        self.framebuffer.unbind();
+        context::with(|ctx| {
+            ctx.viewport(
+                0,
+                0,
+                ctx.drawing_buffer_width(),
+                ctx.drawing_buffer_height(),
+            )
+        });
+
+        result?;
+        Ok(Some(ShadowInfo {
+            light_index,
+            matrix: vp_matrix,
+            bias: settings.bias,
+        }))
+    }
+}
+
+/// 光源の位置から影を落とす範囲を写すカメラを返す
+/// 点光源は影を落とさないため `None` を返す
+fn light_camera(light: &Light, camera: &Camera, settings: &ShadowSettings) -> Option<Camera> {
+    match light {
+        Light::Directional(light) => {
+            let dir = light.dir.normalize();
+            let extent = settings.extent;
+            Some(Camera {
+                pos: camera.look_at - dir * extent,
+                look_at: camera.look_at,
+                up: up_vector(dir),
+                projection: Projection::orthographic(-extent, extent, -extent, extent),
+                near: 0.0,
+                far: extent * 2.0,
+            })
+        }
+        Light::Spot(light) => {
+            let dir = light.dir.normalize();
+            // 画角が180度以上になると投影できないため、少し狭める
+            let fovy = Rad((light.outer_angle.0 * 2.0).min(PI * 0.99));
+            Some(Camera {
+                pos: light.pos,
+                look_at: light.pos + dir,
+                up: up_vector(dir),
+                projection: Projection::perspective(fovy, 1.0),
+                near: 0.1,
+                far: settings.range,
+            })
+        }
+        Light::Point(_) => None,
+    }
+}
+
+/// 視線方向 `dir` と平行にならない上方向を返す
+fn up_vector(dir: Vector3<f32>) -> Vector3<f32> {
+    if dir.z.abs() < 0.99 {
+        vec3(0.0, 0.0, 1.0)
+    } else {
+        vec3(0.0, 1.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use cgmath::vec4;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_directional_light_camera() {
+        let mut camera = Camera::new();
+        camera.look_at = vec3(1.0, 2.0, 0.0);
+        let settings = ShadowSettings::new();
+
+        let light = Light::directional(0.0, 0.0, -1.0);
+        let shadow_camera = light_camera(&light, &camera, &settings).unwrap();
+
+        // 注視点はシャドウマップの中心、深度の範囲の中央に写る
+        let p = shadow_camera.matrix() * vec4(1.0, 2.0, 0.0, 1.0);
+        assert_relative_eq!(p.x / p.w, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(p.y / p.w, 0.0, epsilon = 1e-5);
+        assert_relative_eq!(p.z / p.w, 0.0, epsilon = 1e-5);
+
+        assert!(light_camera(&Light::point(0.0, 0.0, 1.0), &camera, &settings).is_none());
+    }
+}
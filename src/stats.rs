@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+/// 直前の `Renderer::render` で行った描画の統計
+///
+/// シャドウマップへの描画も含む。
+///
+/// ```rust,ignore
+/// renderer.render(&scene, &camera)?;
+/// let stats = renderer.stats();
+/// console::log_1(&format!("draw calls : {}", stats.draw_calls).into());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    /// `drawElements` の呼び出し回数
+    pub draw_calls: usize,
+    /// 描画した三角形の数
+    pub triangles: usize,
+    /// Programの切り替え回数
+    pub program_switches: usize,
+    /// テクスチャのバインド回数
+    pub texture_binds: usize,
+    /// VBOやIBOにデータを転送した回数
+    /// 前回の `render` 以降に `Mesh::new` などで行われた転送も含む
+    pub buffer_uploads: usize,
+    /// カメラに写らないため描画しなかったObjectの数
+    pub culled_objects: usize,
+    /// `render` の呼び出しにかかったCPU時間
+    /// GPUでの処理の完了は待たないため、GPUの処理時間は含まない
+    pub cpu_time: Duration,
+}
+
+impl RenderStats {
+    /// `index_len` 個のインデックスを使った描画を1回数える
+    pub(crate) fn count_draw(&mut self, index_len: i32) {
+        self.draw_calls += 1;
+        self.triangles += index_len as usize / 3;
+    }
+
+    /// Contextでの累計の転送回数 `total` と、前回の `render` 時点での累計 `previous` から、
+    /// その間に行われた転送回数を設定する
+    /// 累計が前回より少ない場合は0とする
+    pub(crate) fn set_buffer_uploads(&mut self, total: usize, previous: usize) {
+        self.buffer_uploads = total.saturating_sub(previous);
+    }
+}
+
+/// 現在時刻をミリ秒で返す
+/// `performance.now()` が使えない環境では0を返す
+pub(crate) fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or(0.0, |performance| performance.now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_count_draw() {
+        let mut stats = RenderStats::default();
+        assert_eq!(stats.draw_calls, 0);
+        assert_eq!(stats.triangles, 0);
+
+        stats.count_draw(36);
+        stats.count_draw(6);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.triangles, 14);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_buffer_uploads() {
+        let mut stats = RenderStats::default();
+        stats.set_buffer_uploads(10, 4);
+        assert_eq!(stats.buffer_uploads, 6);
+
+        stats.set_buffer_uploads(10, 10);
+        assert_eq!(stats.buffer_uploads, 0);
+
+        stats.set_buffer_uploads(3, 10);
+        assert_eq!(stats.buffer_uploads, 0);
+    }
+}
@@ -1,32 +1,53 @@
 use image::RgbaImage;
-use wasm_bindgen::JsValue;
+use napier_webgl::error::Error;
 
 pub use napier_webgl::texture::{GlTexture, MagMethod, MinMethod};
 
 #[derive(Debug, PartialEq)]
 pub struct Texture {
     pub(crate) gl: GlTexture,
+    has_alpha: bool,
 }
 
 impl Texture {
-    pub fn with_image_high(image: &RgbaImage) -> Result<Texture, JsValue> {
-        let gl = GlTexture::new();
-        gl.bind();
+    pub fn with_image_high(image: &RgbaImage) -> Result<Texture, Error> {
+        let gl = GlTexture::new()?;
+        gl.bind()?;
         gl.attach_img(&image, image.width() as i32, image.height() as i32)?;
-        gl.generate_mipmap();
-        gl.set_minify_filter(MinMethod::NearestMipmapLinear);
+        // WebGL1では、縦横が2の冪乗でない画像にはミップマップが使えない
+        if gl.supports_mipmap() {
+            gl.generate_mipmap();
+            gl.set_minify_filter(MinMethod::NearestMipmapLinear);
+        } else {
+            gl.set_minify_filter(MinMethod::Linear);
+        }
         gl.set_magnify_filter(MagMethod::Linear);
         gl.unbind();
-        Ok(Texture { gl })
+        Ok(Texture {
+            gl,
+            has_alpha: has_alpha(image),
+        })
     }
 
-    pub fn with_image_low(image: &RgbaImage) -> Result<Texture, JsValue> {
-        let gl = GlTexture::new();
-        gl.bind();
+    pub fn with_image_low(image: &RgbaImage) -> Result<Texture, Error> {
+        let gl = GlTexture::new()?;
+        gl.bind()?;
         gl.attach_img(&image, image.width() as i32, image.height() as i32)?;
         gl.set_minify_filter(MinMethod::Nearest);
         gl.set_magnify_filter(MagMethod::Nearest);
         gl.unbind();
-        Ok(Texture { gl })
+        Ok(Texture {
+            gl,
+            has_alpha: has_alpha(image),
+        })
     }
+
+    /// 半透明なピクセルを含むかどうか
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+}
+
+fn has_alpha(image: &RgbaImage) -> bool {
+    image.pixels().any(|pixel| pixel[3] < 255)
 }